@@ -0,0 +1,74 @@
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    min: Point3,
+    max: Point3,
+}
+
+impl Aabb {
+    pub fn new(min: Point3, max: Point3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// Smallest box containing both points, in any order.
+    pub fn from_points(a: Point3, b: Point3) -> Aabb {
+        Aabb {
+            min: Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    pub fn surrounding(a: Aabb, b: Aabb) -> Aabb {
+        Aabb::from_points(
+            Aabb::from_points(a.min, b.min).min,
+            Aabb::from_points(a.max, b.max).max)
+    }
+
+    /// Box grown where needed so that no side is thinner than `delta`,
+    /// e.g. for planar shapes.
+    pub fn padded(&self, delta: f64) -> Aabb {
+        let mut min = self.min;
+        let mut max = self.max;
+        for a in 0..3 {
+            if max[a] - min[a] < delta {
+                min[a] -= delta / 2.0;
+                max[a] += delta / 2.0;
+            }
+        }
+        Aabb { min, max }
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        let size = self.max - self.min;
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| self.min + Vec3::new(
+            if i & 1 == 0 { 0.0 } else { size.x() },
+            if i & 2 == 0 { 0.0 } else { size.y() },
+            if i & 4 == 0 { 0.0 } else { size.z() }))
+    }
+
+    pub fn min(&self) -> Point3 { self.min }
+    pub fn max(&self) -> Point3 { self.max }
+
+    /// Parametric range of `r` inside the box, clipped to [t_min, t_max].
+    pub fn hit_range(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for a in 0..3 {
+            let inv_d = 1.0 / r.direction()[a];
+            let mut t0 = (self.min[a] - r.origin()[a]) * inv_d;
+            let mut t1 = (self.max[a] - r.origin()[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::transform::Transform;
+use super::vec::{Point3, Vec3};
+
+/// Camera placement at a given frame.
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub frame: f64,
+    pub look_from: Point3,
+    pub look_at: Point3,
+    pub vertical_field_of_view: f64,
+}
+
+impl Keyframe {
+    /// Keyframe orbited by `degrees` around the vertical axis through the look-at point.
+    pub fn orbited(&self, up: Vec3, degrees: f64) -> Keyframe {
+        let orbit = Transform::translate(self.look_at)
+            * Transform::rotate(up, degrees)
+            * Transform::translate(-1.0 * self.look_at);
+        Keyframe { look_from: orbit.point(self.look_from), ..*self }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Interpolation {
+    Linear,
+    /// Smooth spline through all the keyframes
+    CatmullRom,
+}
+
+/// Keyframed camera path, held still before the first and after the last keyframe.
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    interpolation: Interpolation,
+}
+
+impl CameraPath {
+    /// Loads keyframes from a text file with one keyframe per line:
+    ///
+    /// `<frame> <from x>,<from y>,<from z> <at x>,<at y>,<at z> [<vertical fov>]`
+    ///
+    /// Empty lines and lines starting with `#` are skipped. A missing field
+    /// of view is the one of the previous keyframe, or `default_vfov` at first.
+    pub fn load(path: &Path, default_vfov: f64, interpolation: Interpolation) -> io::Result<CameraPath> {
+        let text = fs::read_to_string(path)?;
+
+        let mut keyframes: Vec<Keyframe> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |message: String| io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", number + 1, message));
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 && fields.len() != 4 {
+                return Err(invalid("expected <frame> <look from> <look at> [<fov>]".to_string()));
+            }
+
+            let frame = fields[0].parse::<f64>().map_err(|e| invalid(e.to_string()))?;
+            let look_from = fields[1].parse::<Point3>().map_err(invalid)?;
+            let look_at = fields[2].parse::<Point3>().map_err(invalid)?;
+            let vertical_field_of_view = match fields.get(3) {
+                Some(fov) => fov.parse::<f64>().map_err(|e| invalid(e.to_string()))?,
+                None => keyframes.last().map_or(default_vfov, |k| k.vertical_field_of_view),
+            };
+
+            if !frame.is_finite() || !vertical_field_of_view.is_finite() {
+                return Err(invalid("frames and fields of view must be finite numbers".to_string()));
+            }
+            if keyframes.last().is_some_and(|k| k.frame >= frame) {
+                return Err(invalid("keyframes must be in increasing frame order".to_string()));
+            }
+            keyframes.push(Keyframe { frame, look_from, look_at, vertical_field_of_view });
+        }
+
+        if keyframes.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "no keyframes"));
+        }
+        Ok(CameraPath { keyframes, interpolation })
+    }
+
+    pub fn at(&self, frame: f64) -> Keyframe {
+        let keys = &self.keyframes;
+        let last = keys.len() - 1;
+        if frame <= keys[0].frame {
+            return Keyframe { frame, ..keys[0] };
+        }
+        if frame >= keys[last].frame {
+            return Keyframe { frame, ..keys[last] };
+        }
+
+        // Segment [i, i + 1] containing the frame
+        let i = keys.partition_point(|k| k.frame <= frame) - 1;
+        let dt = keys[i + 1].frame - keys[i].frame;
+        let s = (frame - keys[i].frame) / dt;
+
+        let interpolate = |value: &dyn Fn(&Keyframe) -> Vec3| match self.interpolation {
+            Interpolation::Linear => value(&keys[i]) + s * (value(&keys[i + 1]) - value(&keys[i])),
+            Interpolation::CatmullRom => {
+                // Cubic Hermite spline with Catmull-Rom tangents, allowing for
+                // unevenly spaced keyframes and using one-sided ones at the ends
+                let tangent = |j: usize| {
+                    let (a, b) = (j.saturating_sub(1), (j + 1).min(last));
+                    (value(&keys[b]) - value(&keys[a])) / (keys[b].frame - keys[a].frame)
+                };
+                let (s2, s3) = (s * s, s * s * s);
+                (2.0 * s3 - 3.0 * s2 + 1.0) * value(&keys[i])
+                    + (s3 - 2.0 * s2 + s) * dt * tangent(i)
+                    + (-2.0 * s3 + 3.0 * s2) * value(&keys[i + 1])
+                    + (s3 - s2) * dt * tangent(i + 1)
+            },
+        };
+
+        Keyframe {
+            frame,
+            look_from: interpolate(&|k| k.look_from),
+            look_at: interpolate(&|k| k.look_at),
+            vertical_field_of_view: interpolate(&|k| Vec3::new(k.vertical_field_of_view, 0.0, 0.0)).x(),
+        }
+    }
+}
+
+/// Inclusive range of frame numbers.
+#[derive(Clone, Copy)]
+pub struct FrameRange {
+    first: u64,
+    last: u64,
+}
+
+impl FrameRange {
+    pub fn single(frame: u64) -> FrameRange {
+        FrameRange { first: frame, last: frame }
+    }
+
+    pub fn first(&self) -> u64 { self.first }
+    pub fn last(&self) -> u64 { self.last }
+    pub fn count(&self) -> u64 { self.last - self.first + 1 }
+}
+
+impl Display for FrameRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.first, self.last)
+    }
+}
+
+impl FromStr for FrameRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let bounds = s.split('-')
+            .map(|a| a.parse::<u64>())
+            .collect::<Vec<_>>();
+
+        match bounds[..] {
+            [Ok(frame)] => Ok(FrameRange::single(frame)),
+            [Ok(first), Ok(last)] if first <= last => Ok(FrameRange { first, last }),
+            _ => Err("Expected format: <FIRST>-<LAST> or <FRAME> (e.g. 1-120).".to_string()),
+        }
+    }
+}
+
+/// Output file of a frame, replacing a printf-style `%d` or `%0<N>d`
+/// in the file name with the frame number. `None` without such a pattern.
+pub fn frame_file(pattern: &Path, frame: u64) -> Option<PathBuf> {
+    let name = pattern.file_name()?.to_str()?;
+    let start = name.find('%')?;
+    let rest = &name[start + 1..];
+    let end = rest.find('d')?;
+
+    let width = match &rest[..end] {
+        "" => 0,
+        digits if digits.starts_with('0') => digits.parse::<usize>().ok()?,
+        _ => return None,
+    };
+
+    let file_name = format!("{}{:0width$}{}", &name[..start], frame, &rest[end + 1..], width = width);
+    Some(pattern.with_file_name(file_name))
+}
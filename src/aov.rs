@@ -0,0 +1,229 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, ImageAttributes, IntegerBounds, Layer,
+    LayerAttributes, WritableImage,
+};
+
+use super::hit::HitRecord;
+use super::ray::Ray;
+use super::scene::Scene;
+use super::vec::{Color, Point3, Vec3};
+
+/// Arbitrary output variables: passes rendered next to the image for compositing.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Aov {
+    /// Distance from the camera (infinite for the background)
+    Depth,
+    /// World-space normal, facing the camera
+    Normal,
+    /// Reflectance of the material
+    Albedo,
+    /// World-space position
+    Position,
+    /// Top-level scene object, from 1 (0 for the background)
+    ObjectId,
+    /// Material in the scene's library, from 1 (0 for the background and unnamed materials)
+    MaterialId,
+    /// Light reaching the camera after one bounce
+    Direct,
+    /// Light reaching the camera after two bounces or more
+    Indirect,
+    /// Light emitted by the surfaces or the background seen by the camera
+    Emission,
+    /// Coverage by the objects of the scene
+    Alpha,
+}
+
+impl Aov {
+    /// Name for layers and file names.
+    pub fn name(self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+
+    /// Channels of the pass over the given pixels.
+    fn channels(self, pixels: &[AovSample]) -> Channels {
+        let scalar = |name: &'static str, f: &dyn Fn(&AovSample) -> f64| {
+            vec![(name, pixels.iter().map(|p| f(p) as f32).collect())]
+        };
+        let vector = |names: [&'static str; 3], f: &dyn Fn(&AovSample) -> Vec3| {
+            (0..3).map(|a| (names[a], pixels.iter().map(|p| f(p)[a] as f32).collect())).collect()
+        };
+
+        match self {
+            Aov::Depth => scalar("Z", &|p| p.depth),
+            Aov::Normal => vector(["X", "Y", "Z"], &|p| p.normal),
+            Aov::Albedo => vector(["R", "G", "B"], &|p| p.albedo),
+            Aov::Position => vector(["X", "Y", "Z"], &|p| p.position),
+            Aov::ObjectId => scalar("id", &|p| p.object_id as f64),
+            Aov::MaterialId => scalar("id", &|p| p.material_id as f64),
+            Aov::Direct => vector(["R", "G", "B"], &|p| p.direct),
+            Aov::Indirect => vector(["R", "G", "B"], &|p| p.indirect),
+            Aov::Emission => vector(["R", "G", "B"], &|p| p.emission),
+            Aov::Alpha => scalar("A", &|p| p.alpha),
+        }
+    }
+}
+
+/// Quantities seen by a camera ray at its first hit.
+#[derive(Clone, Copy)]
+pub struct AovSample {
+    pub depth: f64,
+    pub normal: Vec3,
+    pub albedo: Color,
+    pub position: Point3,
+    pub object_id: usize,
+    pub material_id: usize,
+    pub direct: Color,
+    pub indirect: Color,
+    pub emission: Color,
+    pub alpha: f64,
+}
+
+impl AovSample {
+    /// Sample of a ray escaping the scene.
+    pub fn background() -> AovSample {
+        let black = Color::new(0.0, 0.0, 0.0);
+        AovSample {
+            depth: f64::INFINITY,
+            normal: black,
+            albedo: black,
+            position: black,
+            object_id: 0,
+            material_id: 0,
+            direct: black,
+            indirect: black,
+            emission: black,
+            alpha: 0.0,
+        }
+    }
+
+    /// Records the geometric passes of `rec`, the first hit of camera ray `r`.
+    pub fn record_first_hit(&mut self, rec: &HitRecord, r: &Ray, scene: &Scene) {
+        self.depth = rec.t * r.direction().length();
+        self.normal = rec.normal;
+        self.albedo = rec.material.albedo();
+        self.position = rec.p;
+        self.object_id = rec.object_id;
+        self.material_id = scene.materials.id(rec.material).unwrap_or(0);
+        self.alpha = 1.0;
+    }
+
+    /// Applies `f` to the light passes, e.g. to convert them to RGB.
+    pub fn map_light<F: Fn(Color) -> Color>(self, f: F) -> AovSample {
+        AovSample {
+            direct: f(self.direct),
+            indirect: f(self.indirect),
+            emission: f(self.emission),
+            ..self
+        }
+    }
+}
+
+/// Average of the samples of a pixel. Geometric passes are averaged over
+/// the samples hitting an object, and IDs are the ones of the first of them.
+pub struct AovAccumulator {
+    sum: AovSample,
+    samples: u64,
+    hits: u64,
+}
+
+impl AovAccumulator {
+    pub fn new() -> AovAccumulator {
+        AovAccumulator { sum: AovSample { depth: 0.0, ..AovSample::background() }, samples: 0, hits: 0 }
+    }
+
+    pub fn add(&mut self, sample: &AovSample) {
+        let sum = &mut self.sum;
+        if sample.alpha > 0.0 {
+            if self.hits == 0 {
+                sum.object_id = sample.object_id;
+                sum.material_id = sample.material_id;
+            }
+            sum.depth += sample.depth;
+            sum.normal += sample.normal;
+            sum.position += sample.position;
+            self.hits += 1;
+        }
+        sum.albedo += sample.albedo;
+        sum.direct += sample.direct;
+        sum.indirect += sample.indirect;
+        sum.emission += sample.emission;
+        sum.alpha += sample.alpha;
+        self.samples += 1;
+    }
+
+    pub fn average(&self) -> AovSample {
+        let mut average = AovSample::background();
+        if self.hits > 0 {
+            let hits = self.hits as f64;
+            average.depth = self.sum.depth / hits;
+            average.normal = self.sum.normal / hits;
+            average.position = self.sum.position / hits;
+            average.object_id = self.sum.object_id;
+            average.material_id = self.sum.material_id;
+        }
+        if self.samples > 0 {
+            let samples = self.samples as f64;
+            average.albedo = self.sum.albedo / samples;
+            average.direct = self.sum.direct / samples;
+            average.indirect = self.sum.indirect / samples;
+            average.emission = self.sum.emission / samples;
+            average.alpha = self.sum.alpha / samples;
+        }
+        average
+    }
+}
+
+/// Named channels of values, from the top left corner of an image.
+type Channels = Vec<(&'static str, Vec<f32>)>;
+
+/// Layers of an OpenEXR image.
+pub struct Layers {
+    width: usize,
+    height: usize,
+    layers: Vec<(String, Channels)>,
+}
+
+impl Layers {
+    pub fn new(width: u64, height: u64) -> Layers {
+        Layers { width: width as usize, height: height as usize, layers: Vec::new() }
+    }
+
+    /// Adds a linear RGB layer.
+    pub fn add_color(&mut self, name: &str, pixels: &[Color]) {
+        let channels = ["R", "G", "B"].iter().enumerate()
+            .map(|(a, &channel)| (channel, pixels.iter().map(|c| c[a] as f32).collect()))
+            .collect();
+        self.layers.push((name.to_string(), channels));
+    }
+
+    pub fn add_aov(&mut self, aov: Aov, pixels: &[AovSample]) {
+        self.layers.push((aov.name(), aov.channels(pixels)));
+    }
+
+    /// Writes an OpenEXR file, multi-layer if there are several layers
+    /// and a plain image otherwise.
+    pub fn write(self, path: &Path) -> exr::error::Result<()> {
+        let size = (self.width, self.height);
+        let single = self.layers.len() == 1;
+        let layers: Vec<_> = self.layers.into_iter()
+            .map(|(name, channels)| {
+                let attributes = if single {
+                    LayerAttributes::default()
+                } else {
+                    LayerAttributes::named(name.as_str())
+                };
+                let channels: Vec<_> = channels.into_iter()
+                    .map(|(channel, values)| AnyChannel::new(channel, FlatSamples::F32(values)))
+                    .collect();
+                Layer::new(size, attributes, Encoding::FAST_LOSSLESS, AnyChannels::sort(channels.into()))
+            })
+            .collect();
+
+        Image::from_layers(ImageAttributes::new(IntegerBounds::from_dimensions(size)), layers)
+            .write()
+            .to_file(path)
+    }
+}
@@ -0,0 +1,107 @@
+use std::f64::consts::PI;
+use std::path::Path;
+
+use image::ImageResult;
+use rand::Rng;
+
+use super::random;
+use super::vec::Vec3;
+
+/// Shape of the lens opening, which gives out-of-focus highlights their shape.
+pub enum Aperture {
+    Circle,
+    /// Regular polygon with `blades` sides, rotated by `rotation` degrees.
+    Polygon { blades: u32, rotation: f64 },
+    /// Arbitrary shape given by the brightness of a grayscale image.
+    Image(ApertureImage),
+}
+
+impl Aperture {
+    /// Random point in the aperture, in the z = 0 plane with the lens
+    /// spanning the unit disk.
+    pub fn sample(&self) -> Vec3 {
+        match self {
+            Aperture::Circle => Vec3::random_in_unit_disk(),
+            Aperture::Polygon { blades, rotation } => sample_polygon(*blades, *rotation),
+            Aperture::Image(image) => image.sample(),
+        }
+    }
+}
+
+fn sample_polygon(blades: u32, rotation: f64) -> Vec3 {
+    let mut rng = random::rng();
+
+    // Pick one of the triangles fanning out from the center...
+    let sector = 2.0 * PI / blades as f64;
+    let angle = rotation.to_radians() + sector * rng.gen_range(0..blades) as f64;
+    let a = Vec3::new(angle.cos(), angle.sin(), 0.0);
+    let b = Vec3::new((angle + sector).cos(), (angle + sector).sin(), 0.0);
+
+    // ...and a uniform point inside it
+    let (mut s, mut t): (f64, f64) = (rng.gen(), rng.gen());
+    if s + t > 1.0 {
+        s = 1.0 - s;
+        t = 1.0 - t;
+    }
+    s * a + t * b
+}
+
+/// Aperture image with a 2D cumulative distribution of its brightness,
+/// for importance sampling: a marginal one over the rows and a conditional
+/// one over the pixels of each row.
+pub struct ApertureImage {
+    width: usize,
+    height: usize,
+    row_cdf: Vec<f64>,
+    pixel_cdf: Vec<f64>,
+}
+
+impl ApertureImage {
+    /// Loads an image whose brightness is the transmittance of the aperture.
+    /// Its longer side spans the diameter of the lens.
+    pub fn load(path: &Path) -> ImageResult<ApertureImage> {
+        let image = image::open(path)?.to_luma8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        let mut row_cdf = Vec::with_capacity(height);
+        let mut pixel_cdf = Vec::with_capacity(width * height);
+        let mut total = 0.0;
+        for row in image.rows() {
+            let mut row_total = 0.0;
+            for pixel in row {
+                row_total += pixel.0[0] as f64 / 255.0;
+                pixel_cdf.push(row_total);
+            }
+            total += row_total;
+            row_cdf.push(total);
+        }
+
+        Ok(ApertureImage { width, height, row_cdf, pixel_cdf })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.row_cdf.last().is_some_and(|&total| total > 0.0)
+    }
+
+    fn sample(&self) -> Vec3 {
+        let mut rng = random::rng();
+
+        let total = self.row_cdf[self.height - 1];
+        let xi = rng.gen::<f64>() * total;
+        let y = self.row_cdf.partition_point(|&c| c <= xi);
+        let y = y.min(self.height - 1);
+
+        let row = &self.pixel_cdf[y * self.width..(y + 1) * self.width];
+        let xi = rng.gen::<f64>() * row[self.width - 1];
+        let x = row.partition_point(|&c| c <= xi);
+        let x = x.min(self.width - 1);
+
+        // Uniform within the pixel, with the longer side spanning [-1, 1]
+        // and the first row at the top
+        let scale = 2.0 / self.width.max(self.height) as f64;
+        Vec3::new(
+            (x as f64 + rng.gen::<f64>() - self.width as f64 / 2.0) * scale,
+            (self.height as f64 / 2.0 - y as f64 - rng.gen::<f64>()) * scale,
+            0.0)
+    }
+}
@@ -0,0 +1,450 @@
+// Bidirectional path tracing (Veach 1997, following the formulation of
+// Physically Based Rendering): a camera subpath and a light subpath are traced
+// independently, then every prefix of one is connected to every prefix of the
+// other. Each full path can thus be sampled by several strategies, and the
+// strategies are weighted against each other with the balance heuristic.
+//
+// Strategies ending with a single camera vertex (light subpaths connected
+// straight to the lens) would splat onto arbitrary pixels, and are left out:
+// the weights only account for the strategies that are actually sampled.
+
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use super::aov::AovSample;
+use super::hit::{Hit, HitRecord};
+use super::integrator::{self, Integrator};
+use super::material::Scatter;
+use super::medium::Fog;
+use super::pathlog::Bounce;
+use super::random::{self, Stream};
+use super::ray::Ray;
+use super::scene::Scene;
+use super::spectrum::{self, Wavelengths};
+use super::vec::{Color, Point3, Vec3};
+
+/// Bidirectional path tracer, starting light subpaths from the lights of the scene.
+pub struct Bdpt {
+    max_depth: u64,
+    // Bounces after which subpaths may end by Russian roulette
+    roulette_depth: u64,
+    fog: Option<Fog>,
+}
+
+impl Bdpt {
+    pub fn new(max_depth: u64, fog: Option<Fog>) -> Bdpt {
+        Bdpt { max_depth, roulette_depth: max_depth, fog }
+    }
+
+    /// Bidirectional path tracer whose subpaths may randomly end after
+    /// `roulette_depth` bounces, as with `PathTracer::with_roulette`.
+    pub fn with_roulette(self, roulette_depth: u64) -> Bdpt {
+        Bdpt { roulette_depth, ..self }
+    }
+}
+
+/// Vertex of a camera or light subpath.
+struct Vertex<'a> {
+    p: Point3,
+    // Surface normal, `None` at the camera and inside media
+    normal: Option<Vec3>,
+    kind: Kind<'a>,
+    // Throughput from the start of the subpath, divided by the sampling densities
+    beta: Color,
+    // Densities, per unit area, of sampling the vertex from the start of its
+    // own subpath and from the other end of the path
+    pdf_fwd: f64,
+    pdf_rev: f64,
+    // Specular scattering, which cannot be connected to other vertices
+    delta: bool,
+    wavelengths: Option<Wavelengths>,
+}
+
+enum Kind<'a> {
+    Camera,
+    Light { material: &'a dyn Scatter, u: f64, v: f64, pdf: f64 },
+    Surface(HitRecord<'a>),
+}
+
+impl<'a> Vertex<'a> {
+    fn surface(rec: HitRecord<'a>, beta: Color, wavelengths: Option<Wavelengths>) -> Vertex<'a> {
+        Vertex {
+            p: rec.p,
+            normal: if rec.medium { None } else { Some(rec.normal) },
+            kind: Kind::Surface(rec),
+            beta,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+            wavelengths,
+        }
+    }
+
+    /// Light emitted from the vertex towards `to`, black if it is not on a light.
+    fn emitted(&self, to: Point3) -> Color {
+        let (material, u, v) = match &self.kind {
+            Kind::Light { material, u, v, .. } => (*material, *u, *v),
+            Kind::Surface(rec) if !rec.medium => (rec.material, rec.u, rec.v),
+            _ => return Color::new(0.0, 0.0, 0.0),
+        };
+        if self.normal.is_some_and(|n| n.dot(to - self.p) == 0.0) {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        spectrum::values(self.wavelengths, material.emitted(u, v, self.p))
+    }
+
+    /// BSDF for light coming from `from` and scattered towards `to`.
+    fn bsdf(&self, from: Point3, to: Point3) -> Color {
+        match &self.kind {
+            Kind::Surface(rec) => rec.material.bsdf(rec, self.p - from, to - self.p)
+                .map_or(Color::new(0.0, 0.0, 0.0), |f| spectrum::values(self.wavelengths, f)),
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Converts a density per unit solid angle of leaving the vertex towards
+    /// `next` into a density per unit area at `next`.
+    fn to_area(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let distance_squared = w.dot(w);
+        let cosine = next.normal.map_or(1.0, |n| n.dot(w).abs() / distance_squared.sqrt());
+        pdf * cosine / distance_squared
+    }
+
+    /// Density, per unit area, of sampling `next` from this vertex, reached from `prev`.
+    fn pdf(&self, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        match (&self.kind, prev) {
+            (Kind::Light { .. }, _) => self.emission_pdf(next),
+            (Kind::Surface(rec), Some(prev)) => {
+                let pdf = rec.material.pdf(rec, self.p - prev.p, next.p - self.p);
+                self.to_area(pdf, next)
+            },
+            _ => 0.0,
+        }
+    }
+
+    /// Density, per unit area, of light emitted at this vertex reaching `next`,
+    /// for a light emitting on both sides with cosine-weighted directions.
+    fn emission_pdf(&self, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let cosine = self.normal.map_or(0.0, |n| n.dot(w).abs() / w.length());
+        self.to_area(cosine / (2.0 * PI), next)
+    }
+
+    /// Density, per unit area, of starting a light subpath at this vertex.
+    fn light_origin_pdf(&self, scene: &Scene) -> f64 {
+        match &self.kind {
+            Kind::Light { pdf, .. } => *pdf,
+            Kind::Surface(rec) if !rec.medium => scene.lights.iter()
+                .find(|light| light.object_id() == rec.object_id)
+                .map_or(0.0, |light| 1.0 / (scene.lights.len() as f64 * light.area())),
+            _ => 0.0,
+        }
+    }
+}
+
+impl Bdpt {
+    /// Extends `path` with the vertices hit by `ray` and the rays it scatters into,
+    /// `pdf` being the density per unit solid angle of sampling `ray`. Returns the
+    /// ray escaping the scene, if any, with its throughput.
+    fn walk<'a>(
+        &'a self,
+        scene: &'a Scene,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf: f64,
+        path: &mut Vec<Vertex<'a>>,
+        mut log: Option<&mut Vec<Bounce>>) -> Option<(Ray, Color)> {
+
+        // Camera subpaths also hold the vertex on the lens
+        let max_vertices = match path[0].kind {
+            Kind::Camera => self.max_depth as usize + 1,
+            _ => self.max_depth as usize,
+        };
+        let mut bounces = 0;
+        while path.len() < max_vertices {
+            if let Some(log) = log.as_deref_mut() {
+                log.push(Bounce::new(&ray));
+            }
+
+            let mut hit = scene.world.hit(&ray, 0.001, f64::INFINITY);
+            if let Some(fog) = &self.fog {
+                let t_max = hit.as_ref().map_or(f64::INFINITY, |rec| rec.t);
+                if let Some(rec) = fog.sample_scattering(&ray, 0.001, t_max) {
+                    hit = Some(rec);
+                }
+            }
+            let rec = match hit {
+                Some(rec) => rec,
+                None => return Some((ray, beta)),
+            };
+
+            if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                entry.set_hit(&rec);
+                entry.emitted = spectrum::radiance(&ray, rec.material.emitted(rec.u, rec.v, rec.p));
+            }
+            let scatter = rec.material.scatter(&ray, &rec);
+            let delta = rec.material.bsdf(&rec, ray.direction(), ray.direction()).is_none();
+            let (pdf_next, pdf_prev) = match &scatter {
+                Some((_, scattered)) if !delta => (
+                    rec.material.pdf(&rec, ray.direction(), scattered.direction()),
+                    rec.material.pdf(&rec, -1.0 * scattered.direction(), -1.0 * ray.direction())),
+                _ => (0.0, 0.0),
+            };
+
+            let mut vertex = Vertex::surface(rec, beta, ray.wavelengths());
+            let prev = path.last_mut().unwrap();
+            vertex.pdf_fwd = prev.to_area(pdf, &vertex);
+            vertex.delta = delta;
+            prev.pdf_rev = vertex.to_area(pdf_prev, prev);
+            path.push(vertex);
+
+            let (color, scattered) = match scatter {
+                Some(scatter) => scatter,
+                None => break,
+            };
+            let reflectance = spectrum::reflectance(&ray, &scattered, color);
+            beta = beta * reflectance;
+            bounces += 1;
+
+            // Russian roulette on the throughput, as in the path tracer
+            let survival = if bounces >= self.roulette_depth {
+                beta.x().max(beta.y()).max(beta.z()).min(1.0)
+            } else {
+                1.0
+            };
+            let survives = survival >= 1.0 || random::rng().gen::<f64>() < survival;
+            if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                entry.attenuation = Some(if survives { reflectance / survival } else { reflectance });
+            }
+            if !survives {
+                break;
+            }
+            beta /= survival;
+            pdf = pdf_next;
+            ray = scattered;
+        }
+        None
+    }
+
+    /// Light subpath starting at a random point of a random light.
+    fn light_path<'a>(&'a self, scene: &'a Scene, camera_ray: &Ray) -> Vec<Vertex<'a>> {
+        let mut path = Vec::new();
+        if scene.lights.is_empty() || self.max_depth < 2 {
+            return path;
+        }
+
+        let light = &scene.lights[random::rng().gen_range(0..scene.lights.len())];
+        let (sample, direction, pdf_direction) = light.sample_emission();
+        let pdf = 1.0 / (scene.lights.len() as f64 * light.area());
+        path.push(Vertex {
+            p: sample.p,
+            normal: Some(sample.normal),
+            kind: Kind::Light { material: sample.material, u: sample.u, v: sample.v, pdf },
+            beta: Color::new(1.0, 1.0, 1.0) / pdf,
+            pdf_fwd: pdf,
+            pdf_rev: 0.0,
+            delta: false,
+            wavelengths: camera_ray.wavelengths(),
+        });
+
+        if pdf_direction == 0.0 {
+            return path;
+        }
+
+        let ray = camera_ray.spawn(sample.p, direction);
+        let emitted = path[0].emitted(sample.p + direction);
+        let beta = path[0].beta * emitted * (direction.dot(sample.normal).abs() / pdf_direction);
+        self.walk(scene, ray, beta, pdf_direction, &mut path, None);
+        path
+    }
+
+    /// Unweighted contribution of the path made of `s` light vertices and `t` camera vertices.
+    fn connect(&self, scene: &Scene, r: &Ray, camera: &[Vertex], light: &[Vertex], s: usize, t: usize) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let z = &camera[t - 1];
+        if s == 0 {
+            return z.beta * z.emitted(camera[t - 2].p);
+        }
+
+        let y = &light[s - 1];
+        if z.delta || y.delta {
+            return black;
+        }
+        let f_z = z.bsdf(camera[t - 2].p, y.p);
+        let f_y = if s == 1 { y.emitted(z.p) } else { y.bsdf(light[s - 2].p, z.p) };
+        let contribution = y.beta * f_y * f_z * z.beta;
+        if contribution.near_zero() {
+            return black;
+        }
+
+        let w = y.p - z.p;
+        let distance = w.length();
+        let cosine = |v: &Vertex| v.normal.map_or(1.0, |n| n.dot(w).abs() / distance);
+        let g = cosine(z) * cosine(y) / (distance * distance);
+        if g == 0.0 {
+            return black;
+        }
+        let transmittance = self.transmittance(scene, r, z.p, w / distance, distance);
+        if transmittance == 0.0 {
+            return black;
+        }
+        spectrum::join(z.wavelengths, y.wavelengths) * (g * transmittance) * contribution
+    }
+
+    /// Fraction of light going through the segment of length `distance` from
+    /// `from` along unit `direction`, at the time of camera ray `r`.
+    fn transmittance(&self, scene: &Scene, r: &Ray, from: Point3, direction: Vec3, distance: f64) -> f64 {
+        let ray = r.spawn(from, direction);
+        let t_max = distance - 0.001;
+        let transmittance = scene.world.transmittance(&ray, 0.001, t_max);
+        if transmittance == 0.0 {
+            return 0.0;
+        }
+        // Scattering in the fog also blocks the segment, as often as it attenuates it
+        match &self.fog {
+            Some(fog) if fog.sample_scattering(&ray, 0.001, t_max).is_some() => 0.0,
+            _ => transmittance,
+        }
+    }
+
+    /// Balance heuristic weight of the path made of `s` light vertices and `t`
+    /// camera vertices, among all the strategies sampling it.
+    fn mis_weight(&self, scene: &Scene, camera: &[Vertex], light: &[Vertex], s: usize, t: usize) -> f64 {
+        let remap = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+        let pt = &camera[t - 1];
+        let pt_minus = &camera[t - 2];
+        let qs = s.checked_sub(1).map(|i| &light[i]);
+        let qs_minus = s.checked_sub(2).map(|i| &light[i]);
+
+        // Densities of the connected vertices and their neighbors sampled from the other side
+        let (pt_rev, pt_minus_rev) = match qs {
+            Some(qs) => (qs.pdf(qs_minus, pt), pt.pdf(Some(qs), pt_minus)),
+            None => {
+                let origin = pt.light_origin_pdf(scene);
+                if origin == 0.0 {
+                    // Emitters that are not lights can only be hit by camera subpaths
+                    return 1.0;
+                }
+                (origin, pt.emission_pdf(pt_minus))
+            },
+        };
+        let qs_rev = qs.map_or(0.0, |qs| pt.pdf(Some(pt_minus), qs));
+        let qs_minus_rev = match (qs, qs_minus) {
+            (Some(qs), Some(qs_minus)) => qs.pdf(Some(pt), qs_minus),
+            _ => 0.0,
+        };
+
+        let camera_rev = |i: usize| match t - 1 - i {
+            0 => pt_rev,
+            1 => pt_minus_rev,
+            _ => camera[i].pdf_rev,
+        };
+        let light_rev = |i: usize| match s - 1 - i {
+            0 => qs_rev,
+            1 => qs_minus_rev,
+            _ => light[i].pdf_rev,
+        };
+        // The connected vertices are never specular
+        let camera_delta = |i: usize| i != t - 1 && camera[i].delta;
+        let light_delta = |i: usize| i != s - 1 && light[i].delta;
+
+        // Strategies with fewer camera vertices, down to two of them
+        let mut sum = 0.0;
+        let mut ratio = 1.0;
+        for i in (2..t).rev() {
+            ratio *= remap(camera_rev(i)) / remap(camera[i].pdf_fwd);
+            if !camera_delta(i) && !camera_delta(i - 1) {
+                sum += ratio;
+            }
+        }
+
+        // Strategies with fewer light vertices
+        ratio = 1.0;
+        for i in (0..s).rev() {
+            ratio *= remap(light_rev(i)) / remap(light[i].pdf_fwd);
+            if !light_delta(i) && (i == 0 || !light_delta(i - 1)) {
+                sum += ratio;
+            }
+        }
+        1.0 / (1.0 + sum)
+    }
+}
+
+impl Integrator for Bdpt {
+    fn radiance(&self, r: &Ray, scene: &Scene, aov: &mut AovSample, mut log: Option<&mut Vec<Bounce>>) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let first = log.as_ref().map_or(0, |log| log.len());
+
+        let mut camera = vec![Vertex {
+            p: r.origin(),
+            normal: None,
+            kind: Kind::Camera,
+            beta: Color::new(1.0, 1.0, 1.0),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+            wavelengths: r.wavelengths(),
+        }];
+        let camera_ray = r.spawn(r.origin(), r.direction());
+        let escaped = self.walk(
+            scene, camera_ray, Color::new(1.0, 1.0, 1.0), 0.0, &mut camera, log.as_deref_mut());
+        random::start_stream(Stream::Light);
+        let light = self.light_path(scene, r);
+
+        if let Some(Kind::Surface(rec)) = camera.get(1).map(|z| &z.kind) {
+            aov.record_first_hit(rec, r, scene);
+        }
+
+        // Light gathered by the paths ending at each camera vertex, and by the escaping ray
+        random::start_stream(Stream::Connection);
+        let mut gathered = vec![black; camera.len() + 1];
+        for t in 2..=camera.len() {
+            for s in 0..=light.len() {
+                if (s + t - 1) as u64 > self.max_depth {
+                    continue;
+                }
+                let contribution = self.connect(scene, r, &camera, &light, s, t);
+                if contribution.near_zero() {
+                    continue;
+                }
+                let light = self.mis_weight(scene, &camera, &light, s, t) * contribution;
+                integrator::add_light(aov, (s + t - 2) as u64, light);
+                gathered[t - 1] += light;
+            }
+        }
+
+        let mut escape_beta = black;
+        if let Some((ray, beta)) = &escaped {
+            // Only camera subpaths reach the background
+            let background = *beta * spectrum::radiance(ray, scene.background.color(ray));
+            integrator::add_light(aov, (camera.len() - 1) as u64, background);
+            gathered[camera.len()] += background;
+            escape_beta = *beta;
+            if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                entry.emitted = spectrum::radiance(ray, scene.background.color(ray));
+            }
+        }
+
+        if let Some(log) = log {
+            // Light returned along each camera ray, relative to its throughput
+            let mut total = black;
+            let betas: Vec<Color> = camera.iter().map(|z| z.beta).chain([escape_beta]).collect();
+            for (k, entry) in log[first..].iter_mut().enumerate().rev() {
+                total += gathered[k + 1];
+                let beta = betas[k + 1];
+                entry.color = Color::new(
+                    safe_divide(total.x(), beta.x()),
+                    safe_divide(total.y(), beta.y()),
+                    safe_divide(total.z(), beta.z()));
+            }
+        }
+
+        gathered.into_iter().fold(black, |sum, light| sum + light)
+    }
+}
+
+fn safe_divide(a: f64, b: f64) -> f64 {
+    if b != 0.0 { a / b } else { 0.0 }
+}
@@ -0,0 +1,173 @@
+use std::cell::Cell;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord, World};
+use super::ray::Ray;
+
+thread_local! {
+    static INTERSECTION_TESTS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Number of bounding box and object intersection tests made by the current
+/// thread since the last call.
+pub fn take_intersection_tests() -> u64 {
+    INTERSECTION_TESTS.with(|tests| tests.replace(0))
+}
+
+fn count_intersection_tests(count: u64) {
+    INTERSECTION_TESTS.with(|tests| tests.set(tests.get() + count));
+}
+
+/// Bounding volume hierarchy over the bounded objects of a world.
+/// Unbounded objects (e.g. planes) are tested separately.
+pub struct Bvh {
+    root: Option<BvhNode>,
+    unbounded: World,
+}
+
+struct BvhNode {
+    bbox: Aabb,
+    content: BvhContent,
+}
+
+enum BvhContent {
+    Leaf(Box<dyn Hit>),
+    Interior(Box<BvhNode>, Box<BvhNode>),
+}
+
+/// Object tagging its hits with its index in the world.
+struct Identified {
+    id: usize,
+    object: Box<dyn Hit>,
+}
+
+impl Hit for Identified {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut rec = self.object.hit(r, t_min, t_max)?;
+        rec.object_id = self.id;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.object.transmittance(r, t_min, t_max)
+    }
+}
+
+impl Bvh {
+    /// Hierarchy over `world`, whose objects get IDs from 1 in order.
+    pub fn new(world: World) -> Bvh {
+        let mut bounded = Vec::new();
+        let mut unbounded = World::new();
+        for (i, object) in world.into_iter().enumerate() {
+            let object: Box<dyn Hit> = Box::new(Identified { id: i + 1, object });
+            match object.bounding_box() {
+                Some(bbox) => bounded.push((bbox, object)),
+                None => unbounded.push(object),
+            }
+        }
+
+        let root = if bounded.is_empty() { None } else { Some(BvhNode::build(bounded)) };
+        Bvh { root, unbounded }
+    }
+}
+
+impl BvhNode {
+    fn build(mut objects: Vec<(Aabb, Box<dyn Hit>)>) -> BvhNode {
+        if objects.len() == 1 {
+            let (bbox, object) = objects.pop().unwrap();
+            return BvhNode { bbox, content: BvhContent::Leaf(object) };
+        }
+
+        // Split at the median along the longest axis of the centroids
+        let centroid = |b: &Aabb, axis: usize| b.min()[axis] + b.max()[axis];
+        let mut centroids = objects.iter().map(|(b, _)| {
+            let c = 0.5 * (b.min() + b.max());
+            Aabb::from_points(c, c)
+        });
+        let first = centroids.next().unwrap();
+        let extent = centroids.fold(first, Aabb::surrounding);
+        let size = extent.max() - extent.min();
+        let axis = if size.x() > size.y() && size.x() > size.z() {
+            0
+        } else if size.y() > size.z() {
+            1
+        } else {
+            2
+        };
+
+        objects.sort_by(|(a, _), (b, _)| centroid(a, axis).total_cmp(&centroid(b, axis)));
+        let right = objects.split_off(objects.len() / 2);
+
+        let left = BvhNode::build(objects);
+        let right = BvhNode::build(right);
+        BvhNode {
+            bbox: Aabb::surrounding(left.bbox, right.bbox),
+            content: BvhContent::Interior(Box::new(left), Box::new(right)),
+        }
+    }
+
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        count_intersection_tests(1);
+        self.bbox.hit_range(r, t_min, t_max)?;
+
+        match &self.content {
+            BvhContent::Leaf(object) => {
+                count_intersection_tests(1);
+                object.hit(r, t_min, t_max)
+            },
+            BvhContent::Interior(left, right) => {
+                let left_rec = left.hit(r, t_min, t_max);
+                let t_max = left_rec.as_ref().map_or(t_max, |rec| rec.t);
+                right.hit(r, t_min, t_max).or(left_rec)
+            },
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        count_intersection_tests(1);
+        if self.bbox.hit_range(r, t_min, t_max).is_none() {
+            return 1.0;
+        }
+
+        match &self.content {
+            BvhContent::Leaf(object) => {
+                count_intersection_tests(1);
+                object.transmittance(r, t_min, t_max)
+            },
+            BvhContent::Interior(left, right) => {
+                let left = left.transmittance(r, t_min, t_max);
+                if left == 0.0 { 0.0 } else { left * right.transmittance(r, t_min, t_max) }
+            },
+        }
+    }
+}
+
+impl Hit for Bvh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        count_intersection_tests(self.unbounded.len() as u64);
+        let unbounded_rec = self.unbounded.hit(r, t_min, t_max);
+        let t_max = unbounded_rec.as_ref().map_or(t_max, |rec| rec.t);
+        let bounded_rec = self.root.as_ref().and_then(|root| root.hit(r, t_min, t_max));
+        bounded_rec.or(unbounded_rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match (&self.root, self.unbounded.is_empty()) {
+            (Some(root), true) => Some(root.bbox),
+            _ => None,
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        count_intersection_tests(self.unbounded.len() as u64);
+        let unbounded = self.unbounded.transmittance(r, t_min, t_max);
+        match &self.root {
+            Some(root) if unbounded > 0.0 => unbounded * root.transmittance(r, t_min, t_max),
+            _ => unbounded,
+        }
+    }
+}
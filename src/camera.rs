@@ -1,61 +1,357 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aperture::Aperture;
+use super::random;
 use super::vec::{Vec3, Point3};
 use super::ray::Ray;
 
-pub struct Camera {
+/// Maps normalized image coordinates (u, v) in [0, 1]² to primary rays.
+pub trait Camera: Send + Sync {
+    /// Ray through (u, v), `None` if the point lies outside the projection
+    /// (e.g. the corners of a circular fisheye image).
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray>;
+}
+
+/// Position, orientation and shutter interval shared by all projections.
+/// The camera looks along -w, with v pointing up and u to the right.
+#[derive(Clone, Copy)]
+pub struct View {
     origin: Point3,
+    cu: Vec3,
+    cv: Vec3,
+    cw: Vec3,
+    // Shutter open and close times
+    time0: f64,
+    time1: f64,
+}
+
+impl View {
+    pub fn new(lookfrom: Point3, lookat: Point3, vup: Vec3) -> View {
+        let cw = (lookfrom - lookat).normalized();
+        let cu = vup.cross(cw).normalized();
+        let cv = cw.cross(cu);
+
+        View { origin: lookfrom, cu, cv, cw, time0: 0.0, time1: 0.0 }
+    }
+
+    /// View whose shutter is open between `time0` and `time1`,
+    /// sampling ray times uniformly in between.
+    pub fn with_shutter(self, time0: f64, time1: f64) -> View {
+        View { time0, time1, ..self }
+    }
+
+    fn sample_time(&self) -> f64 {
+        // A closed shutter leaves the random numbers of still images alone
+        if self.time1 == self.time0 {
+            return self.time0;
+        }
+        self.time0 + (self.time1 - self.time0) * random::rng().gen::<f64>()
+    }
+
+    /// Distance of `p` in front of the camera, along the view direction.
+    pub fn depth(&self, p: Point3) -> f64 {
+        (self.origin - p).dot(self.cw)
+    }
+
+    /// Ray from `origin` along the camera-space `direction`, at a sampled time.
+    fn ray(&self, origin: Point3, direction: Vec3) -> Ray {
+        let direction = direction.x() * self.cu + direction.y() * self.cv + direction.z() * self.cw;
+        Ray::new(origin, direction).with_time(self.sample_time())
+    }
+}
+
+/// Standard sensor (or film) formats.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum SensorFormat {
+    /// 36 x 24 mm
+    FullFrame,
+    /// 23.6 x 15.6 mm
+    ApsC,
+    /// 17.3 x 13 mm
+    MicroFourThirds,
+    /// 13.2 x 8.8 mm
+    OneInch,
+    /// 44 x 33 mm
+    MediumFormat,
+}
+
+impl SensorFormat {
+    /// Width and height in millimeters.
+    pub fn size(self) -> (f64, f64) {
+        match self {
+            SensorFormat::FullFrame => (36.0, 24.0),
+            SensorFormat::ApsC => (23.6, 15.6),
+            SensorFormat::MicroFourThirds => (17.3, 13.0),
+            SensorFormat::OneInch => (13.2, 8.8),
+            SensorFormat::MediumFormat => (44.0, 33.0),
+        }
+    }
+
+    /// Height of the largest area of the given aspect ratio fitting in the sensor.
+    fn image_height(self, aspect_ratio: f64) -> f64 {
+        let (width, height) = self.size();
+        height.min(width / aspect_ratio)
+    }
+
+    /// Vertical field of view in degrees of a lens of the given focal length in mm.
+    pub fn vertical_field_of_view(self, focal_length: f64, aspect_ratio: f64) -> f64 {
+        2.0 * (self.image_height(aspect_ratio) / (2.0 * focal_length)).atan().to_degrees()
+    }
+
+    /// Focal length in mm giving a vertical field of view in degrees.
+    pub fn focal_length(self, vfov: f64, aspect_ratio: f64) -> f64 {
+        self.image_height(aspect_ratio) / (2.0 * (vfov.to_radians() / 2.0).tan())
+    }
+}
+
+/// Thin-lens perspective camera.
+#[derive(Clone)]
+pub struct Perspective {
+    view: View,
     lower_left_corner: Point3,
     horizontal: Vec3,
     vertical: Vec3,
-    cu: Vec3,
-    cv: Vec3,
-    lens_radius: f64
+    lens_radius: f64,
+    aperture: Arc<Aperture>,
+    cat_eye: f64,
 }
 
-impl Camera {
+impl Perspective {
     pub fn new(
-        lookfrom: Point3,
-        lookat: Point3,
-        vup: Vec3,
-        vfov: f64, 
+        view: View,
+        vfov: f64,
         aspect_ratio: f64,
         aperture: f64,
-        focus_dist: f64) -> Camera {
+        focus_dist: f64) -> Perspective {
 
         // Vertical field-of-view in degrees
-        let theta = std::f64::consts::PI / 180.0 * vfov;
+        let theta = PI / 180.0 * vfov;
         let viewport_height = 2.0 * (theta / 2.0).tan();
         let viewport_width = aspect_ratio * viewport_height;
 
-        let cw = (lookfrom - lookat).normalized();
-        let cu = vup.cross(cw).normalized();
-        let cv = cw.cross(cu);
-
-        let horizontal = focus_dist * viewport_width * cu;
-        let vertical = focus_dist * viewport_height * cv;
-        let lower_left_corner = lookfrom 
-            - horizontal / 2.0 
-            - vertical / 2.0 
-            - focus_dist * cw;
+        let horizontal = focus_dist * viewport_width * view.cu;
+        let vertical = focus_dist * viewport_height * view.cv;
+        let lower_left_corner = view.origin
+            - horizontal / 2.0
+            - vertical / 2.0
+            - focus_dist * view.cw;
 
-        Camera {
-            origin: lookfrom,
+        Perspective {
+            view,
             lower_left_corner,
             horizontal,
             vertical,
-            cu,
-            cv,
             lens_radius: aperture / 2.0,
+            aperture: Arc::new(Aperture::Circle),
+            cat_eye: 0.0,
         }
     }
 
-    pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
-        let offset = self.cu * rd.x() + self.cv * rd.y();
-        let direction = self.lower_left_corner 
-            + u * self.horizontal 
-            + v * self.vertical 
-            - self.origin
+    /// Camera with the given shape of lens opening.
+    pub fn with_aperture(self, aperture: Arc<Aperture>) -> Perspective {
+        Perspective { aperture, ..self }
+    }
+
+    /// Camera with optical vignetting: away from the image center, the
+    /// aperture is clipped by a disk shifted by `strength` times the offset
+    /// from the center (in half image sizes), giving bokeh a cat's-eye shape.
+    pub fn with_cat_eye(self, strength: f64) -> Perspective {
+        Perspective { cat_eye: strength, ..self }
+    }
+
+    /// Off-axis stereo eye displaced by `offset` along the u axis (negative
+    /// to the left), with zero parallax on the plane at distance `convergence`.
+    /// The focus plane is unchanged.
+    pub fn eye(&self, offset: f64, convergence: f64) -> Perspective {
+        // Project the viewport on the convergence plane from the shifted eye
+        let focus_dist = (self.lower_left_corner + self.horizontal / 2.0 + self.vertical / 2.0
+            - self.view.origin).length();
+        let shift = offset * self.view.cu;
+
+        Perspective {
+            view: View { origin: self.view.origin + shift, ..self.view },
+            lower_left_corner: self.lower_left_corner + (1.0 - focus_dist / convergence) * shift,
+            ..self.clone()
+        }
+    }
+}
+
+impl Camera for Perspective {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let lens_point = self.aperture.sample();
+        if self.cat_eye > 0.0 {
+            // Light blocked by the lens barrel: rejected samples stay black
+            let shift = self.cat_eye * Vec3::new(2.0 * u - 1.0, 2.0 * v - 1.0, 0.0);
+            if (lens_point - shift).length() > 1.0 {
+                return None;
+            }
+        }
+
+        let rd = self.lens_radius * lens_point;
+        let offset = self.view.cu * rd.x() + self.view.cv * rd.y();
+        let direction = self.lower_left_corner
+            + u * self.horizontal
+            + v * self.vertical
+            - self.view.origin
             - offset;
-        Ray::new(self.origin + offset, direction)
+        let time = self.view.sample_time();
+        Some(Ray::new(self.view.origin + offset, direction).with_time(time))
+    }
+}
+
+/// Parallel projection of a `height` tall viewport centered on the view origin.
+pub struct Orthographic {
+    view: View,
+    width: f64,
+    height: f64,
+}
+
+impl Orthographic {
+    pub fn new(view: View, height: f64, aspect_ratio: f64) -> Orthographic {
+        Orthographic { view, width: aspect_ratio * height, height }
+    }
+}
+
+impl Camera for Orthographic {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let origin = self.view.origin
+            + (u - 0.5) * self.width * self.view.cu
+            + (v - 0.5) * self.height * self.view.cv;
+        Some(self.view.ray(origin, Vec3::new(0.0, 0.0, -1.0)))
+    }
+}
+
+/// Equidistant fisheye: the angle from the view direction is proportional
+/// to the distance from the image center, reaching half of `fov` (in degrees)
+/// at the top and bottom edges. Points beyond 180° from the view direction
+/// are left empty.
+pub struct Fisheye {
+    view: View,
+    fov: f64,
+    aspect_ratio: f64,
+}
+
+impl Fisheye {
+    pub fn new(view: View, fov: f64, aspect_ratio: f64) -> Fisheye {
+        Fisheye { view, fov: PI / 180.0 * fov, aspect_ratio }
+    }
+}
+
+impl Camera for Fisheye {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let x = (2.0 * u - 1.0) * self.aspect_ratio;
+        let y = 2.0 * v - 1.0;
+        let r = (x * x + y * y).sqrt();
+
+        let theta = r * self.fov / 2.0;
+        if theta > PI {
+            return None;
+        }
+        let phi = y.atan2(x);
+
+        let direction = Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            -theta.cos());
+        Some(self.view.ray(self.view.origin, direction))
+    }
+}
+
+/// 360° by 180° panorama mapping longitude to u and latitude to v,
+/// with the view direction at the center of the image.
+pub struct Equirectangular {
+    view: View,
+}
+
+impl Equirectangular {
+    pub fn new(view: View) -> Equirectangular {
+        Equirectangular { view }
+    }
+}
+
+impl Camera for Equirectangular {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let longitude = 2.0 * PI * (u - 0.5);
+        let latitude = PI * (v - 0.5);
+
+        let direction = Vec3::new(
+            latitude.cos() * longitude.sin(),
+            latitude.sin(),
+            -latitude.cos() * longitude.cos());
+        Some(self.view.ray(self.view.origin, direction))
+    }
+}
+
+/// Omni-directional stereo eye for a panoramic camera: each ray starts
+/// `offset` away from the view origin (negative to the left), perpendicular
+/// to its horizontal direction, so that the parallax is correct all around.
+/// Rays are turned to meet the center ray at distance `convergence`.
+pub struct Ods<C: Camera> {
+    camera: C,
+    up: Vec3,
+    offset: f64,
+    convergence: f64,
+}
+
+impl<C: Camera> Ods<C> {
+    pub fn new(camera: C, view: View, offset: f64, convergence: f64) -> Ods<C> {
+        Ods { camera, up: view.cv, offset, convergence }
+    }
+}
+
+impl<C: Camera> Camera for Ods<C> {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let r = self.camera.get_ray(u, v)?;
+        let direction = r.direction().normalized();
+
+        // The eyes collapse to the center towards the poles
+        let side = direction.cross(self.up);
+        let shift = self.offset * side;
+
+        Some(r.spawn(r.origin() + shift, self.convergence * direction - shift))
+    }
+}
+
+/// Arrangement of the two eyes of a stereo pair in the output image.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum StereoLayout {
+    /// Left eye on the left half, right eye on the right half
+    SideBySide,
+    /// Left eye on the top half, right eye on the bottom half
+    OverUnder,
+}
+
+/// Stereo pair rendered into a single image.
+pub struct Stereo {
+    left: Box<dyn Camera>,
+    right: Box<dyn Camera>,
+    layout: StereoLayout,
+}
+
+impl Stereo {
+    pub fn new(left: Box<dyn Camera>, right: Box<dyn Camera>, layout: StereoLayout) -> Stereo {
+        Stereo { left, right, layout }
+    }
+
+    /// Aspect ratio of each eye for an output image of the given aspect ratio.
+    pub fn eye_aspect_ratio(layout: StereoLayout, aspect_ratio: f64) -> f64 {
+        match layout {
+            StereoLayout::SideBySide => aspect_ratio / 2.0,
+            StereoLayout::OverUnder => aspect_ratio * 2.0,
+        }
+    }
+}
+
+impl Camera for Stereo {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        match self.layout {
+            StereoLayout::SideBySide if u < 0.5 => self.left.get_ray(2.0 * u, v),
+            StereoLayout::SideBySide => self.right.get_ray(2.0 * u - 1.0, v),
+            StereoLayout::OverUnder if v >= 0.5 => self.left.get_ray(u, 2.0 * v - 1.0),
+            StereoLayout::OverUnder => self.right.get_ray(u, 2.0 * v),
+        }
     }
 }
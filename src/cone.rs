@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::disk::Disk;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Cone with its base centered at `base` and its apex `height` above it
+/// along the y axis, optionally closed by a disk at the base.
+/// Use an `Instance` for other orientations.
+pub struct Cone {
+    base: Point3,
+    radius: f64,
+    height: f64,
+    cap: Option<Disk>,
+    material: Arc<dyn Scatter>,
+}
+
+impl Cone {
+    pub fn new(
+        base: Point3, 
+        radius: f64, 
+        height: f64, 
+        capped: bool, 
+        material: Arc<dyn Scatter>) -> Cone {
+
+        let cap = capped.then(|| 
+            Disk::new(base, Vec3::new(0.0, -1.0, 0.0), radius, material.clone()));
+
+        Cone { base, radius, height, cap, material }
+    }
+
+    fn hit_side(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let o = r.origin() - self.base;
+        let d = r.direction();
+
+        // x² + z² = k² (h - y)²
+        let k2 = (self.radius / self.height).powi(2);
+        let h = self.height - o.y();
+        let a = d.x() * d.x() + d.z() * d.z() - k2 * d.y() * d.y();
+        let half_b = o.x() * d.x() + o.z() * d.z() + k2 * h * d.y();
+        let c = o.x() * o.x() + o.z() * o.z() - k2 * h * h;
+
+        let mut roots = if a.abs() < 1.0e-12 {
+            // Parallel to the surface: a single intersection
+            if half_b == 0.0 { return None; }
+            vec![-c / (2.0 * half_b)]
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let sqrtd = discriminant.sqrt();
+            vec![(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+        };
+        roots.sort_by(f64::total_cmp);
+
+        // Nearest root in range on the half of the double cone below the apex
+        for root in roots {
+            if root < t_min || t_max < root {
+                continue;
+            }
+
+            let p = o + root * d;
+            if p.y() < 0.0 || p.y() > self.height {
+                continue;
+            }
+
+            let outward_normal = Vec3::new(p.x(), k2 * (self.height - p.y()), p.z()).normalized();
+            let phi = (-p.z()).atan2(p.x()) + std::f64::consts::PI;
+            let u = phi / (2.0 * std::f64::consts::PI);
+            let v = p.y() / self.height;
+            return Some(HitRecord::new(r, root, outward_normal, u, v, self.material.as_ref()));
+        }
+
+        None
+    }
+}
+
+impl Hit for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let side = self.hit_side(r, t_min, t_max);
+        let t_max = side.as_ref().map_or(t_max, |rec| rec.t);
+        self.cap.as_ref()
+            .and_then(|cap| cap.hit(r, t_min, t_max))
+            .or(side)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(
+            self.base - Vec3::new(self.radius, 0.0, self.radius),
+            self.base + Vec3::new(self.radius, self.height, self.radius)))
+    }
+}
@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord, World};
+use super::material::Scatter;
+use super::quad::Quad;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Axis-aligned box made of six quads.
+pub struct Cuboid {
+    sides: World,
+}
+
+impl Cuboid {
+    /// Box with opposite corners `a` and `b`.
+    pub fn new(a: Point3, b: Point3, material: Arc<dyn Scatter>) -> Cuboid {
+        let bounds = Aabb::from_points(a, b);
+        let (min, max) = (bounds.min(), bounds.max());
+
+        let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+        // Front, right, back, left, top, bottom
+        let sides: World = vec![
+            Box::new(Quad::new(Point3::new(min.x(), min.y(), max.z()), dx, dy, material.clone())),
+            Box::new(Quad::new(Point3::new(max.x(), min.y(), max.z()), (-1.0) * dz, dy, material.clone())),
+            Box::new(Quad::new(Point3::new(max.x(), min.y(), min.z()), (-1.0) * dx, dy, material.clone())),
+            Box::new(Quad::new(Point3::new(min.x(), min.y(), min.z()), dz, dy, material.clone())),
+            Box::new(Quad::new(Point3::new(min.x(), max.y(), max.z()), dx, (-1.0) * dz, material.clone())),
+            Box::new(Quad::new(Point3::new(min.x(), min.y(), min.z()), dx, dz, material)),
+        ];
+
+        Cuboid { sides }
+    }
+}
+
+impl Hit for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.sides.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.sides.bounding_box()
+    }
+}
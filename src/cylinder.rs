@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::disk::Disk;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Cylinder standing on `base` along the y axis, optionally closed
+/// by disks at both ends. Use an `Instance` for other orientations.
+pub struct Cylinder {
+    base: Point3,
+    radius: f64,
+    height: f64,
+    caps: Option<[Disk; 2]>,
+    material: Arc<dyn Scatter>,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point3, 
+        radius: f64, 
+        height: f64, 
+        capped: bool, 
+        material: Arc<dyn Scatter>) -> Cylinder {
+
+        let caps = capped.then(|| [
+            Disk::new(base, Vec3::new(0.0, -1.0, 0.0), radius, material.clone()),
+            Disk::new(base + Vec3::new(0.0, height, 0.0), Vec3::new(0.0, 1.0, 0.0), radius, material.clone()),
+        ]);
+
+        Cylinder { base, radius, height, caps, material }
+    }
+
+    fn hit_side(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let o = r.origin() - self.base;
+        let d = r.direction();
+
+        let a = d.x() * d.x() + d.z() * d.z();
+        if a == 0.0 {
+            // Parallel to the axis
+            return None;
+        }
+        let half_b = o.x() * d.x() + o.z() * d.z();
+        let c = o.x() * o.x() + o.z() * o.z() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        // Nearest root in range whose point lies within the height
+        let sqrtd = discriminant.sqrt();
+        for root in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+            if root < t_min || t_max < root {
+                continue;
+            }
+
+            let p = o + root * d;
+            if p.y() < 0.0 || p.y() > self.height {
+                continue;
+            }
+
+            let outward_normal = Vec3::new(p.x(), 0.0, p.z()) / self.radius;
+            let phi = (-p.z()).atan2(p.x()) + std::f64::consts::PI;
+            let u = phi / (2.0 * std::f64::consts::PI);
+            let v = p.y() / self.height;
+            return Some(HitRecord::new(r, root, outward_normal, u, v, self.material.as_ref()));
+        }
+
+        None
+    }
+}
+
+impl Hit for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest = self.hit_side(r, t_min, t_max);
+        for cap in self.caps.iter().flatten() {
+            let t_max = closest.as_ref().map_or(t_max, |rec| rec.t);
+            if let Some(rec) = cap.hit(r, t_min, t_max) {
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(
+            self.base - Vec3::new(self.radius, 0.0, self.radius),
+            self.base + Vec3::new(self.radius, self.height, self.radius)))
+    }
+}
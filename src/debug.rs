@@ -0,0 +1,101 @@
+use clap::ValueEnum;
+
+use super::bvh;
+use super::hit::Hit;
+use super::ray::Ray;
+use super::scene::Scene;
+use super::vec::Color;
+
+/// Diagnostic shadings replacing the path tracer.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DebugView {
+    /// World-space normal at the first hit, mapped from [-1, 1] to [0, 1]
+    Normals,
+    /// Distance to the first hit, from white (near) to black (far), mid-gray
+    /// at the distance between the suggested viewpoint and look-at point of the scene
+    Depth,
+    /// Texture coordinates at the first hit, as red and green
+    Uv,
+    /// Green where rays hit the front of surfaces, red where they hit the back
+    Facing,
+    /// One color per type of material
+    Material,
+    /// Number of bounces of the path, as a heatmap up to the maximum depth
+    Bounces,
+    /// Number of bounding box and object intersection tests of the camera ray, as a heatmap
+    Cost,
+}
+
+/// Displayed color for ray `r`, to be written as is, without gamma.
+pub fn shade(view: DebugView, r: &Ray, scene: &Scene, max_depth: u64) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+
+    bvh::take_intersection_tests();
+    let hit = scene.world.hit(r, 0.001, f64::INFINITY);
+    let tests = bvh::take_intersection_tests();
+
+    match (view, hit) {
+        (DebugView::Cost, _) => {
+            // Logarithmic scale, saturating at 1024 tests
+            heatmap((1.0 + tests as f64).log2() / 10.0)
+        },
+        (DebugView::Bounces, _) => {
+            let bounces = bounces(r, scene, max_depth);
+            heatmap(bounces as f64 / max_depth.max(1) as f64)
+        },
+        (_, None) => black,
+        (DebugView::Normals, Some(rec)) => 0.5 * (rec.normal + Color::new(1.0, 1.0, 1.0)),
+        (DebugView::Depth, Some(rec)) => {
+            let distance = rec.t * r.direction().length();
+            let scale = (scene.look_at - scene.look_from).length();
+            let shade = 1.0 / (1.0 + distance / scale);
+            Color::new(shade, shade, shade)
+        },
+        (DebugView::Uv, Some(rec)) => Color::new(rec.u, rec.v, 0.0),
+        (DebugView::Facing, Some(rec)) => {
+            if rec.front_face {
+                Color::new(0.0, 1.0, 0.0)
+            } else {
+                Color::new(1.0, 0.0, 0.0)
+            }
+        },
+        (DebugView::Material, Some(rec)) => name_color(rec.material.name()),
+    }
+}
+
+/// Number of surfaces a path bounces off before escaping, being absorbed
+/// or reaching the depth limit.
+fn bounces(r: &Ray, scene: &Scene, max_depth: u64) -> u64 {
+    let mut ray = None;
+    for bounce in 0..max_depth {
+        let r: &Ray = ray.as_ref().unwrap_or(r);
+        let scattered = scene.world.hit(r, 0.001, f64::INFINITY)
+            .and_then(|rec| rec.material.scatter(r, &rec));
+        match scattered {
+            Some((_, scattered)) => ray = Some(scattered),
+            None => return bounce,
+        }
+    }
+    max_depth
+}
+
+/// Blue, green, yellow and red for `t` from 0 to 1.
+fn heatmap(t: f64) -> Color {
+    let stops = [
+        Color::new(0.0, 0.0, 1.0),
+        Color::new(0.0, 1.0, 0.0),
+        Color::new(1.0, 1.0, 0.0),
+        Color::new(1.0, 0.0, 0.0),
+    ];
+    let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let i = (x as usize).min(stops.len() - 2);
+    let s = x - i as f64;
+    (1.0 - s) * stops[i] + s * stops[i + 1]
+}
+
+/// Bright color derived from a hash of `name` (FNV-1a).
+fn name_color(name: &str) -> Color {
+    let hash = name.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    let channel = |shift: u32| 0.25 + 0.75 * ((hash >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
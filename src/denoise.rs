@@ -0,0 +1,95 @@
+// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010): a 5x5 B3
+// spline kernel applied with holes of increasing size, whose weights drop
+// across differences of color, normal, albedo and depth, so that noise is
+// smoothed within surfaces but not across their edges or textures.
+
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use super::aov::AovSample;
+use super::vec::Color;
+
+/// Number of passes, the last one with a kernel of 4 x 2^ITERATIONS pixels.
+const ITERATIONS: u32 = 5;
+
+/// Spread of the edge-stopping functions.
+const SIGMA_COLOR: f64 = 1.0;
+const SIGMA_NORMAL: f64 = 0.3;
+const SIGMA_ALBEDO: f64 = 0.1;
+const SIGMA_DEPTH: f64 = 0.05;
+
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Denoised linear colors of a `width` x `height` image, from the top left
+/// corner, guided by the passes of its pixels.
+pub fn denoise(colors: &[Color], guides: &[AovSample], width: u64, height: u64) -> Vec<Color> {
+    let (width, height) = (width as usize, height as usize);
+    let mut colors = colors.to_vec();
+    for iteration in 0..ITERATIONS {
+        let step = 1 << iteration;
+        // Finer passes keep smaller color differences
+        let sigma_color = SIGMA_COLOR / (1 << iteration) as f64;
+        colors = (0..width * height)
+            .into_par_iter()
+            .map(|p| filter(&colors, guides, width, height, p, step, sigma_color))
+            .collect();
+    }
+    colors
+}
+
+fn filter(
+    colors: &[Color],
+    guides: &[AovSample],
+    width: usize,
+    height: usize,
+    p: usize,
+    step: isize,
+    sigma_color: f64) -> Color {
+
+    let (x, y) = ((p % width) as isize, (p / width) as isize);
+    let (color, guide) = (colors[p], &guides[p]);
+    let tone_mapped = tone_map(color);
+
+    let mut sum = Color::new(0.0, 0.0, 0.0);
+    let mut total = 0.0;
+    for (dy, ky) in KERNEL.iter().enumerate() {
+        for (dx, kx) in KERNEL.iter().enumerate() {
+            let qx = x + (dx as isize - 2) * step;
+            let qy = y + (dy as isize - 2) * step;
+            if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                continue;
+            }
+            let q = qy as usize * width + qx as usize;
+            let other = &guides[q];
+
+            let weight = kx * ky
+                * gaussian((tone_map(colors[q]) - tone_mapped).length(), sigma_color)
+                * gaussian((other.normal - guide.normal).length(), SIGMA_NORMAL)
+                * gaussian((other.albedo - guide.albedo).length(), SIGMA_ALBEDO)
+                * depth_weight(guide.depth, other.depth);
+            sum += weight * colors[q];
+            total += weight;
+        }
+    }
+    // The center pixel always has a positive weight
+    sum / total
+}
+
+/// Compresses bright values, so that lights don't dominate color differences.
+fn tone_map(color: Color) -> Color {
+    color.map(&|c| c / (1.0 + c))
+}
+
+fn gaussian(distance: f64, sigma: f64) -> f64 {
+    (-distance * distance / (sigma * sigma)).exp()
+}
+
+/// Weight of the relative difference of depths, keeping the background apart.
+fn depth_weight(depth: f64, other: f64) -> f64 {
+    if depth.is_finite() && other.is_finite() {
+        gaussian((depth - other).abs() / depth.max(1e-6), SIGMA_DEPTH)
+    } else if depth == other {
+        1.0
+    } else {
+        0.0
+    }
+}
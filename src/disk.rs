@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Flat disk facing `normal`, or an annulus if the inner radius is positive.
+pub struct Disk {
+    center: Point3,
+    normal: Vec3,
+    inner_radius: f64,
+    radius: f64,
+    // Orthonormal axes in the plane of the disk, for texture coordinates
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Arc<dyn Scatter>,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: Arc<dyn Scatter>) -> Disk {
+        Disk::annulus(center, normal, 0.0, radius, material)
+    }
+
+    pub fn annulus(
+        center: Point3, 
+        normal: Vec3, 
+        inner_radius: f64, 
+        radius: f64, 
+        material: Arc<dyn Scatter>) -> Disk {
+
+        let normal = normal.normalized();
+        let a = if normal.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let bitangent = normal.cross(a).normalized();
+        let tangent = bitangent.cross(normal);
+
+        Disk { center, normal, inner_radius, radius, tangent, bitangent, material }
+    }
+}
+
+impl Hit for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(r.direction());
+
+        // No hit if the ray is parallel to the disk
+        if denom.abs() < 1.0e-8 {
+            return None;
+        }
+
+        let t = self.normal.dot(self.center - r.origin()) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let planar = r.at(t) - self.center;
+        let distance = planar.length();
+        if distance > self.radius || distance < self.inner_radius {
+            return None;
+        }
+
+        // u is the angle around the center, v the distance from the inner edge
+        let phi = (-planar.dot(self.bitangent)).atan2(planar.dot(self.tangent)) + std::f64::consts::PI;
+        let u = phi / (2.0 * std::f64::consts::PI);
+        let v = (distance - self.inner_radius) / (self.radius - self.inner_radius);
+
+        Some(HitRecord::new(r, t, self.normal, u, v, self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let n = self.normal;
+        let extent = self.radius * Vec3::new(
+            (1.0 - n.x() * n.x()).max(0.0).sqrt(),
+            (1.0 - n.y() * n.y()).max(0.0).sqrt(),
+            (1.0 - n.z() * n.z()).max(0.0).sqrt());
+        Some(Aabb::from_points(self.center - extent, self.center + extent).padded(1.0e-4))
+    }
+}
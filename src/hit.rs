@@ -1,3 +1,4 @@
+use super::aabb::Aabb;
 use super::material::Scatter;
 use super::vec::{Vec3, Point3};
 use super::ray::Ray;
@@ -7,11 +8,24 @@ pub struct HitRecord<'a>  {
     pub normal: Vec3,
     pub material: &'a dyn Scatter,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
+    // Top-level scene object hit, from 1 (0 if unknown)
+    pub object_id: usize,
+    // Scattering inside a medium, where the normal is meaningless
+    pub medium: bool,
 }
 
 impl<'a> HitRecord<'a> {
-    pub fn new(r: &Ray, t: f64, outward_normal: Vec3, material: &'a dyn Scatter) -> HitRecord<'a> {
+    pub fn new(
+        r: &Ray, 
+        t: f64, 
+        outward_normal: Vec3, 
+        u: f64, 
+        v: f64, 
+        material: &'a dyn Scatter) -> HitRecord<'a> {
+
         let p = r.at(t);
         let front_face = r.direction().dot(outward_normal) < 0.0;
         let normal = if front_face {
@@ -20,18 +34,28 @@ impl<'a> HitRecord<'a> {
             (-1.0) * outward_normal
         };
         
-        HitRecord { p, normal, material, t, front_face }
+        HitRecord { p, normal, material, t, u, v, front_face, object_id: 0, medium: false }
     }
 }
 
 pub trait Hit : Send + Sync {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    /// Box enclosing the object, `None` if it is unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Unbiased estimate of the fraction of light going through the object
+    /// between `t_min` and `t_max` along `r`, for occlusion queries.
+    /// Anything hit blocks the light, unless the object knows better.
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if self.hit(r, t_min, t_max).is_some() { 0.0 } else { 1.0 }
+    }
 }
 
 pub type World = Vec<Box<dyn Hit>>;
 
 impl Hit for World {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut tmp_rec = None;
         let mut closest_so_far = t_max;
 
@@ -44,4 +68,21 @@ impl Hit for World {
 
         tmp_rec
     }
-}
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.iter().map(|object| object.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |acc, b| Some(Aabb::surrounding(acc, b?)))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.0;
+        for object in self {
+            transmittance *= object.transmittance(r, t_min, t_max);
+            if transmittance == 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+}
\ No newline at end of file
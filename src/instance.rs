@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::ray::Ray;
+use super::transform::{AnimatedTransform, Transform};
+
+/// Transformed copy of a shared object.
+pub struct Instance {
+    object: Arc<dyn Hit>,
+    transform: AnimatedTransform,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hit>, transform: Transform) -> Instance {
+        Instance { object, transform: AnimatedTransform::fixed(transform) }
+    }
+
+    /// Instance moving from the `start` to the `end` transform 
+    /// between `time0` and `time1`.
+    pub fn moving(
+        object: Arc<dyn Hit>, 
+        start: Transform, 
+        end: Transform, 
+        time0: f64, 
+        time1: f64) -> Instance {
+
+        Instance { object, transform: AnimatedTransform::new(start, end, time0, time1) }
+    }
+
+    /// `r` in the space of the object, when the instance has the given transform.
+    /// The direction is not normalized, so that the ray parameter t is the same
+    /// in both spaces.
+    fn object_ray(&self, r: &Ray, transform: &Transform) -> Ray {
+        let to_object = transform.inverse();
+        r.spawn(to_object.point(r.origin()), to_object.vector(r.direction()))
+    }
+}
+
+impl Hit for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // Intersect in object space
+        let transform = self.transform.at(r.time());
+        let object_ray = self.object_ray(r, &transform);
+
+        let mut rec = self.object.hit(&object_ray, t_min, t_max)?;
+
+        // Affine transforms preserve the sign of the normal's dot product
+        // with the ray direction, so front_face is still valid
+        rec.p = r.at(rec.t);
+        rec.normal = transform.normal(rec.normal).normalized();
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box().map(|b| self.transform.aabb(&b))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let object_ray = self.object_ray(r, &self.transform.at(r.time()));
+        self.object.transmittance(&object_ray, t_min, t_max)
+    }
+}
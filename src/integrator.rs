@@ -0,0 +1,126 @@
+use rand::Rng;
+
+use super::aov::AovSample;
+use super::hit::Hit;
+use super::medium::Fog;
+use super::pathlog::{self, Bounce};
+use super::random;
+use super::ray::Ray;
+use super::scene::Scene;
+use super::spectrum;
+use super::vec::Color;
+
+/// Estimator of the light arriving along camera rays.
+pub trait Integrator : Send + Sync {
+    /// Radiance along camera ray `r`. `aov` receives the quantities seen at
+    /// the first hit, and `log`, if given, the rays traced.
+    fn radiance(&self, r: &Ray, scene: &Scene, aov: &mut AovSample, log: Option<&mut Vec<Bounce>>) -> Color;
+}
+
+/// Unidirectional path tracer, following one scattered ray per bounce.
+pub struct PathTracer {
+    max_depth: u64,
+    // Bounces after which paths may end by Russian roulette
+    roulette_depth: u64,
+    fog: Option<Fog>,
+}
+
+impl PathTracer {
+    pub fn new(max_depth: u64, fog: Option<Fog>) -> PathTracer {
+        PathTracer { max_depth, roulette_depth: max_depth, fog }
+    }
+
+    /// Path tracer whose paths may randomly end after `roulette_depth` bounces,
+    /// the more likely the less light they carry. Paths that go on carry
+    /// proportionally more light, so the image stays unbiased.
+    pub fn with_roulette(self, roulette_depth: u64) -> PathTracer {
+        PathTracer { roulette_depth, ..self }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, r: &Ray, scene: &Scene, aov: &mut AovSample, mut log: Option<&mut Vec<Bounce>>) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let first = log.as_ref().map_or(0, |log| log.len());
+
+        let mut radiance = black;
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = None;
+        for bounce in 0..self.max_depth {
+            let r: &Ray = ray.as_ref().unwrap_or(r);
+            if let Some(log) = log.as_deref_mut() {
+                log.push(Bounce::new(r));
+            }
+
+            let mut hit = scene.world.hit(r, 0.001, f64::INFINITY);
+            if let Some(fog) = &self.fog {
+                // Free-flight sampling: scatter in the fog if it happens before the surface hit
+                let t_max = hit.as_ref().map_or(f64::INFINITY, |rec| rec.t);
+                if let Some(rec) = fog.sample_scattering(r, 0.001, t_max) {
+                    hit = Some(rec);
+                }
+            }
+
+            let rec = match hit {
+                Some(rec) => rec,
+                None => {
+                    let background = spectrum::radiance(r, scene.background.color(r));
+                    add_light(aov, bounce, throughput * background);
+                    radiance += throughput * background;
+                    if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                        entry.emitted = background;
+                    }
+                    break;
+                },
+            };
+
+            let emitted = spectrum::radiance(r, rec.material.emitted(rec.u, rec.v, rec.p));
+            add_light(aov, bounce, throughput * emitted);
+            radiance += throughput * emitted;
+            if bounce == 0 {
+                aov.record_first_hit(&rec, r, scene);
+            }
+            if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                entry.set_hit(&rec);
+                entry.emitted = emitted;
+            }
+
+            let (color, scattered) = match rec.material.scatter(r, &rec) {
+                Some(scatter) => scatter,
+                None => break,
+            };
+            let reflectance = spectrum::reflectance(r, &scattered, color);
+            throughput = throughput * reflectance;
+
+            // Russian roulette: go on with the probability of the largest throughput
+            let survival = if bounce + 1 >= self.roulette_depth {
+                throughput.x().max(throughput.y()).max(throughput.z()).min(1.0)
+            } else {
+                1.0
+            };
+            let survives = survival >= 1.0 || random::rng().gen::<f64>() < survival;
+            if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                entry.attenuation = Some(if survives { reflectance / survival } else { reflectance });
+            }
+            if !survives {
+                break;
+            }
+            throughput /= survival;
+            ray = Some(scattered);
+        }
+
+        if let Some(log) = log {
+            pathlog::gather(&mut log[first..]);
+        }
+        radiance
+    }
+}
+
+/// Adds light reaching the camera after `bounce` bounces to the light passes.
+pub fn add_light(aov: &mut AovSample, bounce: u64, light: Color) {
+    match bounce {
+        0 => aov.emission += light,
+        1 => aov.direct += light,
+        _ => aov.indirect += light,
+    }
+}
@@ -0,0 +1,59 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use super::material::Scatter;
+use super::quad::Quad;
+use super::random;
+use super::vec::{Point3, Vec3};
+
+/// Emitting quad that integrators can sample, also present in the world
+/// as top-level object `object_id`. It emits on both sides.
+pub struct AreaLight {
+    quad: Quad,
+    object_id: usize,
+}
+
+/// Point sampled on a light.
+pub struct LightSample<'a> {
+    pub p: Point3,
+    pub normal: Vec3,
+    pub u: f64,
+    pub v: f64,
+    pub material: &'a dyn Scatter,
+}
+
+impl AreaLight {
+    pub fn new(quad: Quad, object_id: usize) -> AreaLight {
+        AreaLight { quad, object_id }
+    }
+
+    pub fn object_id(&self) -> usize {
+        self.object_id
+    }
+
+    pub fn area(&self) -> f64 {
+        self.quad.area()
+    }
+
+    /// Uniformly distributed point on the light.
+    pub fn sample(&self) -> LightSample<'_> {
+        let (p, u, v) = self.quad.sample();
+        LightSample { p, normal: self.quad.normal(), u, v, material: self.quad.material() }
+    }
+
+    /// Uniformly distributed point on the light, with a cosine-weighted unit
+    /// direction leaving it on a random side, and the density of the direction
+    /// per unit solid angle (0 for grazing directions).
+    pub fn sample_emission(&self) -> (LightSample<'_>, Vec3, f64) {
+        let sample = self.sample();
+        let side = if random::rng().gen::<bool>() { sample.normal } else { -1.0 * sample.normal };
+        let mut direction = side + Vec3::random_in_unit_sphere().normalized();
+        if direction.near_zero() {
+            direction = side;
+        }
+        let direction = direction.normalized();
+        let pdf = (direction.dot(side) / (2.0 * PI)).max(0.0);
+        (sample, direction, pdf)
+    }
+}
@@ -1,22 +1,108 @@
+mod aabb;
+mod aov;
+mod animation;
+mod aperture;
+mod bdpt;
+mod bvh;
 mod camera;
+mod cone;
+mod cuboid;
+mod cylinder;
+mod debug;
+mod denoise;
+mod disk;
 mod hit;
+mod instance;
+mod integrator;
+mod light;
 mod material;
+mod medium;
+mod mlt;
+mod pathlog;
+mod photon;
+mod plane;
+mod quad;
+mod random;
 mod ray;
 mod render;
+mod roots;
 mod scene;
 mod size;
+mod spectrum;
 mod sphere;
+mod torus;
+mod transform;
 mod vec;
+mod volume;
 
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
-use camera::Camera;
-use render::Render;
-use size::Size;
-use vec::{Point3, Vec3};
+use aabb::Aabb;
+use aov::Aov;
+use animation::{CameraPath, FrameRange, Interpolation, Keyframe};
+use aperture::{Aperture, ApertureImage};
+use camera::{Camera, Equirectangular, Fisheye, Ods, Orthographic, Perspective, SensorFormat, Stereo, StereoLayout, View};
+use debug::DebugView;
+use medium::{Fog, PhaseFunction};
+use mlt::Metropolis;
+use pathlog::LogFormat;
+use photon::PhotonMapper;
+use render::{Render, RenderError};
+use hit::Hit;
+use bdpt::Bdpt;
+use integrator::PathTracer;
+use size::{Crop, Pixel, Size};
+use vec::{Color, Point3, Vec3};
+use volume::{GridResolution, VoxelGrid};
 
+#[derive(Clone, Copy, ValueEnum)]
+enum Scene {
+    /// Final scene of Ray Tracing in One Weekend
+    Random,
+    /// Dispersive glass spheres
+    Dispersion,
+    /// Smoke, fog and subsurface-looking spheres
+    Volumes,
+    /// Thousands of instances of shared spheres
+    Instances,
+    /// Cloud from a voxel grid (procedural unless --volume-file is given)
+    Cloud,
+    /// Cornell box lit by an area light
+    CornellBox,
+    /// Cylinders, cones, disks and tori
+    Quadrics,
+    /// Bouncing spheres and a sliding box, blurred by the shutter interval
+    MotionBlur,
+    /// Glass spheres under small lights, casting caustics
+    Caustics,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Projection {
+    /// Thin-lens perspective camera
+    Perspective,
+    /// Parallel projection framing the look-at point like the perspective camera
+    Orthographic,
+    /// Equidistant fisheye, 180° vertically unless --vertical-field-of-view is given
+    Fisheye,
+    /// 360° panorama (use a 2:1 image size)
+    Equirectangular,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Integrator {
+    /// Unidirectional path tracing
+    Path,
+    /// Bidirectional path tracing, connecting camera and light subpaths (scenes with lights only)
+    Bdpt,
+    /// Photon mapping, with caustic and global photon maps (scenes with lights only)
+    Photon,
+    /// Primary sample space Metropolis light transport over bidirectional paths
+    Mlt,
+}
 
 #[derive(Parser)]
 #[command(author = "Alessandro Passaro", version, about)]
@@ -25,53 +111,636 @@ struct Arguments {
     
     image_file: PathBuf,
 
+    #[arg(long, value_enum, default_value_t = Scene::Random)]
+    scene: Scene,
+
+    /// Density grid for the cloud scene (.vol, or raw 8-bit with --volume-resolution)
+    #[arg(long)]
+    volume_file: Option<PathBuf>,
+
+    /// Resolution of a raw density grid
+    #[arg(long)]
+    volume_resolution: Option<GridResolution>,
+
     #[arg(short='i', long, default_value_t = Size::new(1200, 800))]
     image_size: Size,
 
+    /// Render only the region x0,y0,x1,y1 from the top left corner, in pixels or normalized
+    #[arg(long)]
+    crop: Option<Crop>,
+
+    /// With --crop, write the full frame with black outside of the region
+    #[arg(long, requires = "crop")]
+    full_frame: bool,
+
     #[arg(short, long, default_value_t = 500)]
     samples_per_pixel: u64,
 
-    #[arg(short, long, default_value_t = 50)]
+    #[arg(long, value_enum, default_value_t = Integrator::Path)]
+    integrator: Integrator,
+
+    /// Maximum number of bounces of a path
+    #[arg(short, long, default_value_t = 1000)]
     max_depth: u64,
 
-    #[arg(short, long, default_value_t = 20.0)]
-    vertical_field_of_view: f64,
+    /// Number of bounces after which paths may end by Russian roulette
+    #[arg(long, default_value_t = 5)]
+    roulette_depth: u64,
+
+    /// Number of photons stored in the global photon map
+    #[arg(long, default_value_t = 200_000)]
+    photons: usize,
+
+    /// Number of photons stored in the caustic photon map
+    #[arg(long, default_value_t = 100_000)]
+    caustic_photons: usize,
+
+    /// Number of nearest photons used to estimate radiance
+    #[arg(long, default_value_t = 50)]
+    photon_neighbors: usize,
+
+    /// Largest radius photons are looked up in [default: unlimited]
+    #[arg(long)]
+    photon_radius: Option<f64>,
+
+    /// Number of Markov chains of Metropolis light transport
+    #[arg(long, default_value_t = 1000)]
+    mlt_chains: u64,
+
+    /// Number of independent paths estimating the brightness of the image and starting the chains
+    #[arg(long, default_value_t = 100_000)]
+    mlt_bootstrap: u64,
+
+    /// Standard deviation of the small mutations of random numbers
+    #[arg(long, default_value_t = 0.01)]
+    mlt_sigma: f64,
+
+    /// Probability of mutations replacing all the random numbers of a path
+    #[arg(long, default_value_t = 0.3)]
+    mlt_large_step: f64,
+
+    /// Camera position [default: depends on the scene]
+    #[arg(long, allow_hyphen_values = true, conflicts_with = "camera_path")]
+    look_from: Option<Point3>,
+
+    /// Point the camera looks at [default: depends on the scene]
+    #[arg(long, allow_hyphen_values = true, conflicts_with = "camera_path")]
+    look_at: Option<Point3>,
+
+    /// Up direction of the camera
+    #[arg(long, allow_hyphen_values = true, default_value = "0,1,0")]
+    up: Vec3,
+
+    /// Keyframes of an animated camera, one '<frame> <look from> <look at> [<fov>]' per line
+    #[arg(long)]
+    camera_path: Option<PathBuf>,
+
+    /// Interpolation between camera keyframes
+    #[arg(long, value_enum, default_value_t = Interpolation::CatmullRom)]
+    interpolation: Interpolation,
+
+    /// Orbit the camera once around the look-at point over the frame range
+    #[arg(long)]
+    turntable: bool,
+
+    /// Frames to render, with the frame number replacing '%d' or '%04d' in the image file name
+    #[arg(long)]
+    frames: Option<FrameRange>,
+
+    #[arg(long, value_enum, default_value_t = Projection::Perspective)]
+    projection: Projection,
+
+    /// Render a stereo pair in the given layout (omni-directional stereo for panoramic projections)
+    #[arg(long, value_enum)]
+    stereo: Option<StereoLayout>,
+
+    /// Distance between the eyes of a stereo pair, in scene units
+    #[arg(long, default_value_t = 0.065)]
+    interocular_distance: f64,
+
+    /// Distance of zero parallax for stereo pairs [default: distance to the look-at point]
+    #[arg(long)]
+    convergence_distance: Option<f64>,
+
+    /// Number of aperture blades, giving polygonal bokeh (0 for a circular aperture)
+    #[arg(long, default_value_t = 0)]
+    aperture_blades: u32,
+
+    /// Rotation of the aperture blades in degrees
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    aperture_rotation: f64,
+
+    /// Grayscale image giving the shape of the aperture (overrides --aperture-blades)
+    #[arg(long)]
+    aperture_image: Option<PathBuf>,
+
+    /// Strength of the cat's-eye vignetting of the bokeh towards the image edges (0 disables it)
+    #[arg(long, default_value_t = 0.0)]
+    cat_eye: f64,
+
+    /// Vertical field of view in degrees [default: depends on the scene]
+    #[arg(short, long)]
+    vertical_field_of_view: Option<f64>,
+
+    /// Focal length of the lens in mm, instead of a field of view
+    #[arg(long, conflicts_with = "vertical_field_of_view")]
+    focal_length: Option<f64>,
+
+    /// Sensor format, relating focal lengths to fields of view
+    #[arg(long, value_enum, default_value_t = SensorFormat::FullFrame)]
+    sensor: SensorFormat,
+
+    /// Diameter of the lens aperture (0 for a pinhole camera) [default: 0.1]
+    #[arg(long)]
+    aperture: Option<f64>,
+
+    /// Aperture as an f-number, with scene units taken as meters
+    #[arg(long, conflicts_with = "aperture")]
+    f_number: Option<f64>,
+
+    /// Distance of the plane in focus [default: 10]
+    #[arg(long)]
+    focus_distance: Option<f64>,
+
+    /// Focus on the surface seen at the given pixel
+    #[arg(long, conflicts_with = "focus_distance")]
+    autofocus: Option<Pixel>,
 
     #[arg(short, long, default_value_t = 42)]
     random_seed: u64,
+
+    /// Time at which the shutter opens [default: depends on the scene]
+    #[arg(long, allow_negative_numbers = true)]
+    shutter_open: Option<f64>,
+
+    /// Time at which the shutter closes, equal to the opening time for no
+    /// motion blur [default: depends on the scene]
+    #[arg(long, allow_negative_numbers = true)]
+    shutter_close: Option<f64>,
+
+    /// Passes to write next to the image, as extra layers of an .exr image or as separate .exr files
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "debug_view")]
+    aov: Vec<Aov>,
+
+    /// Shade pixels with a diagnostic view instead of path tracing
+    #[arg(long, value_enum)]
+    debug_view: Option<DebugView>,
+
+    /// Log every ray traced for the samples of a pixel instead of rendering
+    #[arg(long)]
+    trace_pixel: Option<Pixel>,
+
+    /// Format of the --trace-pixel log
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    trace_format: LogFormat,
+
+    /// Limit the radiance of light reaching the camera after two bounces or more, per sample
+    #[arg(long)]
+    clamp_indirect: Option<f64>,
+
+    /// Report each sample discarded for being NaN or infinite
+    #[arg(long)]
+    log_invalid_samples: bool,
+
+    /// Fail if more than this fraction of the samples are NaN or infinite
+    #[arg(long)]
+    max_invalid_samples: Option<f64>,
+
+    /// Denoise the image, guided by the albedo, normals and depth of the scene
+    #[arg(long, conflicts_with = "debug_view")]
+    denoise: bool,
+
+    /// Trace wavelength samples instead of RGB (enables dispersion)
+    #[arg(long)]
+    spectral: bool,
+
+    /// Density of a homogeneous ground fog (0 disables it)
+    #[arg(long, default_value_t = 0.0)]
+    fog_density: f64,
+
+    /// Height of the top of the fog layer
+    #[arg(long, default_value_t = 3.0, allow_negative_numbers = true)]
+    fog_height: f64,
+
+    /// Single-scattering albedo of the fog
+    #[arg(long, default_value_t = 0.9)]
+    fog_albedo: f64,
+
+    /// Henyey-Greenstein asymmetry of the fog, in (-1, 1) (0 is isotropic)
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    fog_anisotropy: f64,
 }
 
 impl Arguments {
     fn image_file(&self) -> &Path { self.image_file.as_ref() }
+    fn scene(&self) -> Scene { self.scene }
+    fn volume_file(&self) -> Option<&Path> { self.volume_file.as_deref() }
+    fn volume_resolution(&self) -> Option<GridResolution> { self.volume_resolution }
     fn image_size(&self) -> Size { self.image_size }
+    fn crop(&self) -> Option<Crop> { self.crop }
+    fn full_frame(&self) -> bool { self.full_frame }
     fn samples_per_pixel(&self) -> u64 { self.samples_per_pixel }
+    fn integrator(&self) -> Integrator { self.integrator }
     fn max_depth(&self) -> u64 { self.max_depth }
-    fn vertical_field_of_view(&self) -> f64 { self.vertical_field_of_view }
+    fn roulette_depth(&self) -> u64 { self.roulette_depth }
+    fn photons(&self) -> usize { self.photons }
+    fn caustic_photons(&self) -> usize { self.caustic_photons }
+    fn photon_neighbors(&self) -> usize { self.photon_neighbors }
+    fn photon_radius(&self) -> Option<f64> { self.photon_radius }
+    fn look_from(&self) -> Option<Point3> { self.look_from }
+    fn look_at(&self) -> Option<Point3> { self.look_at }
+    fn up(&self) -> Vec3 { self.up }
+    fn camera_path(&self) -> Option<&Path> { self.camera_path.as_deref() }
+    fn interpolation(&self) -> Interpolation { self.interpolation }
+    fn turntable(&self) -> bool { self.turntable }
+    fn frames(&self) -> Option<FrameRange> { self.frames }
+    fn projection(&self) -> Projection { self.projection }
+    fn stereo(&self) -> Option<StereoLayout> { self.stereo }
+    fn interocular_distance(&self) -> f64 { self.interocular_distance }
+    fn convergence_distance(&self) -> Option<f64> { self.convergence_distance }
+    fn cat_eye(&self) -> f64 { self.cat_eye }
+    fn vertical_field_of_view(&self) -> Option<f64> { self.vertical_field_of_view }
+    fn focal_length(&self) -> Option<f64> { self.focal_length }
+    fn sensor(&self) -> SensorFormat { self.sensor }
+    fn aperture_diameter(&self) -> Option<f64> { self.aperture }
+    fn f_number(&self) -> Option<f64> { self.f_number }
+    fn focus_distance(&self) -> Option<f64> { self.focus_distance }
+    fn autofocus(&self) -> Option<Pixel> { self.autofocus }
     fn random_seed(&self) -> u64 { self.random_seed }
+    fn aovs(&self) -> Vec<Aov> { self.aov.clone() }
+    fn denoise(&self) -> bool { self.denoise }
+    fn debug_view(&self) -> Option<DebugView> { self.debug_view }
+    fn clamp_indirect(&self) -> Option<f64> { self.clamp_indirect }
+    fn log_invalid_samples(&self) -> bool { self.log_invalid_samples }
+    fn max_invalid_samples(&self) -> Option<f64> { self.max_invalid_samples }
+    fn trace_pixel(&self) -> Option<Pixel> { self.trace_pixel }
+    fn trace_format(&self) -> LogFormat { self.trace_format }
+    fn spectral(&self) -> bool { self.spectral }
+
+    fn aperture(&self) -> Result<Aperture, String> {
+        if let Some(path) = self.aperture_image.as_deref() {
+            let image = ApertureImage::load(path)
+                .map_err(|e| format!("'{}': {}", path.display(), e))?;
+            if image.is_empty() {
+                return Err(format!("'{}' is black", path.display()));
+            }
+            Ok(Aperture::Image(image))
+        } else if self.aperture_blades == 0 {
+            Ok(Aperture::Circle)
+        } else if self.aperture_blades < 3 {
+            Err("an aperture needs at least 3 blades".to_string())
+        } else {
+            Ok(Aperture::Polygon { blades: self.aperture_blades, rotation: self.aperture_rotation })
+        }
+    }
+
+    fn metropolis(&self) -> Result<Option<Metropolis>, String> {
+        if !matches!(self.integrator, Integrator::Mlt) {
+            return Ok(None);
+        }
+        if self.mlt_chains == 0 || self.mlt_bootstrap == 0 {
+            return Err("at least one chain and one bootstrap path are needed".to_string());
+        }
+        if self.mlt_sigma <= 0.0 || !(0.0..=1.0).contains(&self.mlt_large_step) {
+            return Err("mutations need a positive size and a large step probability in [0, 1]".to_string());
+        }
+        if !self.aov.is_empty() || self.denoise {
+            return Err("passes and denoising are not available".to_string());
+        }
+        if self.trace_pixel.is_some() {
+            return Err("pixels cannot be traced".to_string());
+        }
+        if self.log_invalid_samples {
+            return Err("invalid samples are not logged".to_string());
+        }
+        Ok(Some(Metropolis::new(self.mlt_chains, self.mlt_bootstrap)
+            .with_mutations(self.mlt_sigma, self.mlt_large_step)))
+    }
+
+    /// Shutter open and close times, from the scene unless given.
+    fn shutter(&self, scene: &scene::Scene) -> (f64, f64) {
+        (self.shutter_open.unwrap_or(scene.shutter_open), self.shutter_close.unwrap_or(scene.shutter_close))
+    }
+
+    fn fog(&self) -> Option<Fog> {
+        if self.fog_density > 0.0 {
+            Some(Fog::new(
+                self.fog_density,
+                self.fog_height,
+                Color::new(self.fog_albedo, self.fog_albedo, self.fog_albedo),
+                PhaseFunction::HenyeyGreenstein(self.fog_anisotropy)))
+        } else {
+            None
+        }
+    }
+}
+
+fn load_volume(args: &Arguments) -> Result<(VoxelGrid, Aabb), String> {
+    let default_bounds = Aabb::new(Point3::new(-2.0, 0.0, -2.0), Point3::new(2.0, 4.0, 2.0));
+
+    let path = match args.volume_file() {
+        Some(path) => path,
+        None => return Ok((scene::procedural_cloud(args.random_seed()), default_bounds)),
+    };
+    let describe = |e: std::io::Error| format!("'{}': {}", path.display(), e);
+
+    if path.extension().is_some_and(|ext| ext == "vol") {
+        VoxelGrid::load_vol(path).map_err(describe)
+    } else {
+        let resolution = args.volume_resolution()
+            .ok_or("raw volumes require --volume-resolution")?;
+        let grid = VoxelGrid::load_raw(path, resolution).map_err(describe)?;
+        Ok((grid, default_bounds))
+    }
+}
+
+fn build_camera(args: &Arguments, scene: &scene::Scene, pose: &Keyframe) -> Result<Box<dyn Camera>, String> {
+    let look_from = pose.look_from;
+    let look_at = pose.look_at;
+    let up = args.up();
+
+    // Degenerate orientations would give NaN axes
+    let direction = look_at - look_from;
+    if direction.near_zero() {
+        return Err("the look-from and look-at points must differ".to_string());
+    }
+    if up.near_zero() || up.normalized().cross(direction.normalized()).near_zero() {
+        return Err("the up vector must not be parallel to the view direction".to_string());
+    }
+
+    let (shutter_open, shutter_close) = args.shutter(scene);
+    let view = View::new(look_from, look_at, up)
+        .with_shutter(shutter_open, shutter_close);
+    let distance = direction.length();
+
+    let mut aspect_ratio = args.image_size().aspect_ratio();
+    if let Some(layout) = args.stereo() {
+        aspect_ratio = Stereo::eye_aspect_ratio(layout, aspect_ratio);
+    }
+
+    // Lens, from either a field of view or a focal length
+    let sensor = args.sensor();
+    let (vfov, focal_length) = match args.focal_length() {
+        Some(f) if f <= 0.0 => return Err("the focal length must be positive".to_string()),
+        Some(f) => (sensor.vertical_field_of_view(f, aspect_ratio), f),
+        None => (pose.vertical_field_of_view, sensor.focal_length(pose.vertical_field_of_view, aspect_ratio)),
+    };
+    let lens_diameter = match (args.aperture_diameter(), args.f_number()) {
+        (Some(d), _) if d < 0.0 => return Err("the aperture must not be negative".to_string()),
+        (Some(d), _) => d,
+        (None, Some(n)) if n <= 0.0 => return Err("the f-number must be positive".to_string()),
+        (None, Some(n)) => focal_length / n / 1000.0,
+        (None, None) => 0.1,
+    };
+
+    // Camera for an eye displaced by the given offset, 0 for a single view
+    let convergence = args.convergence_distance().unwrap_or(distance);
+    let aperture = Arc::new(args.aperture()?);
+    let eye = |offset: f64, lens_diameter: f64, focus: f64| -> Result<Box<dyn Camera>, String> {
+        let stereo = offset != 0.0;
+        Ok(match args.projection() {
+            Projection::Perspective => {
+                let camera = Perspective::new(view, vfov, aspect_ratio, lens_diameter, focus)
+                    .with_aperture(aperture.clone())
+                    .with_cat_eye(args.cat_eye());
+                Box::new(camera.eye(offset, convergence))
+            },
+            Projection::Orthographic if stereo => {
+                return Err("orthographic projections have no stereo parallax".to_string());
+            },
+            Projection::Orthographic => {
+                // Same framing as the perspective camera at the look-at point
+                let height = 2.0 * distance * (vfov.to_radians() / 2.0).tan();
+                Box::new(Orthographic::new(view, height, aspect_ratio))
+            },
+            Projection::Fisheye => {
+                let fov = args.vertical_field_of_view().unwrap_or(180.0);
+                let camera = Fisheye::new(view, fov, aspect_ratio);
+                if stereo {
+                    Box::new(Ods::new(camera, view, offset, convergence))
+                } else {
+                    Box::new(camera)
+                }
+            },
+            Projection::Equirectangular => {
+                let camera = Equirectangular::new(view);
+                if stereo {
+                    Box::new(Ods::new(camera, view, offset, convergence))
+                } else {
+                    Box::new(camera)
+                }
+            },
+        })
+    };
+
+    let rig = |lens_diameter: f64, focus: f64| -> Result<Box<dyn Camera>, String> {
+        match args.stereo() {
+            Some(layout) => {
+                if args.interocular_distance() <= 0.0 || convergence <= 0.0 {
+                    return Err("stereo distances must be positive".to_string());
+                }
+                let half = args.interocular_distance() / 2.0;
+                Ok(Box::new(Stereo::new(
+                    eye(-half, lens_diameter, focus)?,
+                    eye(half, lens_diameter, focus)?,
+                    layout)))
+            },
+            None => eye(0.0, lens_diameter, focus),
+        }
+    };
+
+    let focus = match (args.focus_distance(), args.autofocus()) {
+        (Some(d), _) if d <= 0.0 => return Err("the focus distance must be positive".to_string()),
+        (Some(d), _) => d,
+        (None, Some(pixel)) => autofocus(rig(0.0, 1.0)?.as_ref(), &view, scene, args.image_size(), pixel)?,
+        (None, None) => 10.0,
+    };
+
+    rig(lens_diameter, focus)
+}
+
+/// Distance of the surface seen through the center of `pixel` by a pinhole camera.
+fn autofocus(
+    camera: &dyn Camera,
+    view: &View,
+    scene: &scene::Scene,
+    image_size: Size,
+    pixel: Pixel) -> Result<f64, String> {
+
+    let (u, v) = pixel.uv(image_size)
+        .ok_or(format!("autofocus pixel {} is outside of the image", pixel))?;
+    camera.get_ray(u, v)
+        .and_then(|r| scene.world.hit(&r, 0.001, f64::INFINITY))
+        .map(|rec| view.depth(rec.p))
+        .filter(|&depth| depth > 0.0)
+        .ok_or(format!("nothing to focus on at pixel {}", pixel))
 }
 
 fn main() {
 
     let args = Arguments::parse();
 
-    let camera = Camera::new(
-        Point3::new(13.0, 4.0, -2.0),
-        Point3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        args.vertical_field_of_view(), 
-        args.image_size().aspect_ratio(),
-        0.1,
-        10.0);
-
-    let render = Render::new(
-        scene::random_scene(args.random_seed()),
+    let scene = match args.scene() {
+        Scene::Random => scene::random_scene(args.random_seed()),
+        Scene::Dispersion => scene::dispersion_scene(args.random_seed()),
+        Scene::Volumes => scene::volumes_scene(),
+        Scene::Instances => scene::instances_scene(args.random_seed()),
+        Scene::Cloud => {
+            let (grid, bounds) = load_volume(&args).unwrap_or_else(|e| {
+                eprintln!("Error reading volume: {}.", e);
+                std::process::exit(1);
+            });
+            scene::cloud_scene(grid, bounds)
+        },
+        Scene::CornellBox => scene::cornell_box(),
+        Scene::Quadrics => scene::quadrics_scene(),
+        Scene::MotionBlur => scene::motion_blur_scene(args.random_seed()),
+        Scene::Caustics => scene::caustics_scene(),
+    };
+
+    let (shutter_open, shutter_close) = args.shutter(&scene);
+    if shutter_close < shutter_open {
+        eprintln!("The shutter cannot close before it opens.");
+        std::process::exit(1);
+    }
+
+    // Camera placement, possibly animated
+    let still = Keyframe {
+        frame: 0.0,
+        look_from: args.look_from().unwrap_or(scene.look_from),
+        look_at: args.look_at().unwrap_or(scene.look_at),
+        vertical_field_of_view: args.vertical_field_of_view().unwrap_or(scene.vertical_field_of_view),
+    };
+    let path = args.camera_path().map(|path| {
+        CameraPath::load(path, still.vertical_field_of_view, args.interpolation()).unwrap_or_else(|e| {
+            eprintln!("Error reading camera path '{}': {}.", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    let frames = args.frames().unwrap_or(FrameRange::single(0));
+    let pose = |frame: u64| {
+        let mut pose = match &path {
+            Some(path) => path.at(frame as f64),
+            None => still,
+        };
+        if args.turntable() {
+            let turn = (frame - frames.first()) as f64 / frames.count() as f64;
+            pose = pose.orbited(args.up(), 360.0 * turn);
+        }
+        pose
+    };
+
+    let animated = frames.count() > 1;
+    if animated && animation::frame_file(args.image_file(), frames.first()).is_none() {
+        eprintln!("The image file name needs a frame number pattern such as '%04d'.");
+        std::process::exit(1);
+    }
+
+    let camera = build_camera(&args, &scene, &pose(frames.first())).unwrap_or_else(|e| {
+        eprintln!("Invalid camera: {}.", e);
+        std::process::exit(1);
+    });
+
+    let metropolis = args.metropolis().unwrap_or_else(|e| {
+        eprintln!("Invalid Metropolis light transport: {}.", e);
+        std::process::exit(1);
+    });
+
+    let integrator: Box<dyn integrator::Integrator> = match args.integrator() {
+        Integrator::Path => Box::new(
+            PathTracer::new(args.max_depth(), args.fog()).with_roulette(args.roulette_depth())),
+        // Metropolis light transport mutates bidirectional paths
+        Integrator::Bdpt | Integrator::Mlt => {
+            if scene.lights.is_empty() {
+                eprintln!("Bidirectional path tracing needs a scene with lights to start light subpaths from.");
+                std::process::exit(1);
+            }
+            Box::new(Bdpt::new(args.max_depth(), args.fog()).with_roulette(args.roulette_depth()))
+        },
+        Integrator::Photon => {
+            if args.fog().is_some() {
+                eprintln!("Photon mapping does not render fog.");
+                std::process::exit(1);
+            }
+            if scene.lights.is_empty() {
+                eprintln!("Photon mapping needs a scene with lights to emit photons from.");
+                std::process::exit(1);
+            }
+            if args.photon_neighbors() == 0 || args.photon_radius().is_some_and(|r| r <= 0.0) {
+                eprintln!("Photon lookups need at least one neighbor and a positive radius.");
+                std::process::exit(1);
+            }
+            let mapper = PhotonMapper::new(
+                &scene, args.max_depth(), args.photons(), args.caustic_photons(), args.random_seed())
+                .with_density_estimation(args.photon_neighbors(), args.photon_radius().unwrap_or(f64::INFINITY));
+            let (global, caustic) = mapper.photon_counts();
+            println!("Photons: {} global, {} caustic", global, caustic);
+            Box::new(mapper)
+        },
+    };
+
+    let mut render = Render::new(
+        scene,
         camera,
+        integrator,
         args.samples_per_pixel(),
-        args.max_depth(),
-        args.image_size());
+        args.image_size(),
+        args.spectral())
+        .with_seed(args.random_seed())
+        .with_aovs(args.aovs())
+        .with_denoise(args.denoise())
+        .with_debug_view(args.debug_view(), args.max_depth())
+        .with_clamp_indirect(args.clamp_indirect())
+        .with_invalid_samples(args.log_invalid_samples(), args.max_invalid_samples())
+        .with_metropolis(metropolis);
+
+    if let Some(crop) = args.crop() {
+        let pixels = crop.pixels(args.image_size()).unwrap_or_else(|| {
+            eprintln!("The crop region must be a non-empty part of the {} image.", args.image_size());
+            std::process::exit(1);
+        });
+        render = render.with_crop(pixels, args.full_frame());
+    }
+
+    if let Some(pixel) = args.trace_pixel() {
+        let (i, j) = pixel.indices(args.image_size()).unwrap_or_else(|| {
+            eprintln!("The traced pixel {} is outside of the {} image.", pixel, args.image_size());
+            std::process::exit(1);
+        });
+        let (color, samples) = render.trace_pixel(i, j);
+        print!("{}", pathlog::format(args.trace_format(), (pixel.x(), pixel.y()), color, &samples));
+        return;
+    }
+
+    // The scene and its BVH are shared by all frames
+    for frame in frames.first()..=frames.last() {
+        if frame != frames.first() {
+            let camera = build_camera(&args, render.scene(), &pose(frame)).unwrap_or_else(|e| {
+                eprintln!("Invalid camera at frame {}: {}.", frame, e);
+                std::process::exit(1);
+            });
+            render.set_camera(camera);
+        }
+
+        let image_file = animation::frame_file(args.image_file(), frame)
+            .unwrap_or_else(|| args.image_file().to_path_buf());
+        if animated {
+            println!("Frame {} of {}: '{}'", frame - frames.first() + 1, frames.count(), image_file.display());
+        }
 
-    if let Err(e) = render.render_to_image(args.image_file()) { 
-        eprintln!("Error writing to '{}': {}.", args.image_file().display(), e);
-        std::process::exit(2);
+        match render.render_to_image(&image_file) {
+            Ok(()) => (),
+            Err(e @ RenderError::InvalidSamples { .. }) => {
+                eprintln!("Render failed: {}.", e);
+                std::process::exit(3);
+            },
+            Err(e) => {
+                eprintln!("Error writing to '{}': {}.", image_file.display(), e);
+                std::process::exit(2);
+            },
+        }
+        if render.invalid_samples() > 0 {
+            eprintln!("Warning: {} samples were NaN or infinite and counted as black.", render.invalid_samples());
+        }
     }
 }
@@ -1,12 +1,46 @@
+use std::collections::HashMap;
+use std::ops::Index;
+use std::sync::Arc;
+
 use rand::Rng;
 
-use super::vec::{Vec3, Color};
+use super::vec::{Vec3, Color, Point3};
 use super::ray::Ray;
+use super::random;
 use super::hit::HitRecord;
+use super::spectrum::{Dispersion, D_LINE};
 
 
 pub trait Scatter : Send + Sync {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Color, Ray)>;
+
+    /// Light emitted at the given surface point.
+    fn emitted(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Overall reflectance, e.g. as a guide for compositing and denoising.
+    fn albedo(&self) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Value of the BSDF (or phase function) for light arriving along `incoming`
+    /// and scattered along `scattered`, `None` for specular materials whose
+    /// scattered directions cannot be connected to arbitrary points.
+    fn bsdf(&self, _hit: &HitRecord, _incoming: Vec3, _scattered: Vec3) -> Option<Color> {
+        None
+    }
+
+    /// Density, per unit solid angle, of `scatter` choosing `scattered` for a
+    /// ray arriving along `incoming`.
+    fn pdf(&self, _hit: &HitRecord, _incoming: Vec3, _scattered: Vec3) -> f64 {
+        0.0
+    }
+
+    /// Name of the type of material, for diagnostics.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
 
 pub struct Lambertian {
@@ -20,16 +54,38 @@ impl Lambertian {
 }
 
 impl Scatter for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Color, Ray)> {
         let mut scatter_direction = hit.normal + Vec3::random_in_unit_sphere().normalized();
         if scatter_direction.near_zero() {
             // Catch degenerate scatter direction
             scatter_direction = hit.normal;
         }
 
-        let scattered = Ray::new(hit.p, scatter_direction);
+        let scattered = ray.spawn(hit.p, scatter_direction);
         Some((self.albedo, scattered))
     }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
+
+    fn bsdf(&self, hit: &HitRecord, incoming: Vec3, scattered: Vec3) -> Option<Color> {
+        // Reflection only, on the side the normal faces
+        if incoming.dot(hit.normal) < 0.0 && scattered.dot(hit.normal) > 0.0 {
+            Some(self.albedo / std::f64::consts::PI)
+        } else {
+            Some(Color::new(0.0, 0.0, 0.0))
+        }
+    }
+
+    fn pdf(&self, hit: &HitRecord, incoming: Vec3, scattered: Vec3) -> f64 {
+        // Cosine-weighted directions around the normal
+        if incoming.dot(hit.normal) < 0.0 {
+            (scattered.normalized().dot(hit.normal) / std::f64::consts::PI).max(0.0)
+        } else {
+            0.0
+        }
+    }
 }
 
 pub struct Metal {
@@ -46,7 +102,7 @@ impl Metal {
 impl Scatter for Metal {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Color, Ray)> {
         let reflected = ray.direction().reflect(hit.normal).normalized();
-        let scattered = Ray::new(hit.p, 
+        let scattered = ray.spawn(hit.p, 
             reflected + self.fuzz * Vec3::random_in_unit_sphere());
 
         if scattered.direction().dot(hit.normal) > 0.0 {
@@ -55,15 +111,29 @@ impl Scatter for Metal {
             None
         }
     }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
 }
 
 pub struct Dielectric {
-    index_of_refraction: f64
+    index_of_refraction: f64,
+    dispersion: Option<Dispersion>,
 }
 
 impl Dielectric {
     pub fn new(index_of_refraction: f64) -> Dielectric {
-        Dielectric { index_of_refraction }
+        Dielectric { index_of_refraction, dispersion: None }
+    }
+
+    /// Dielectric whose index of refraction depends on the wavelength.
+    /// Outside spectral mode, the index at the sodium D line is used.
+    pub fn dispersive(dispersion: Dispersion) -> Dielectric {
+        Dielectric { 
+            index_of_refraction: dispersion.index_of_refraction(D_LINE), 
+            dispersion: Some(dispersion),
+        }
     }
 
     fn reflectance(cosine: f64, refraction_ratio: f64) -> f64 {
@@ -75,17 +145,25 @@ impl Dielectric {
 
 impl Scatter for Dielectric {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Color, Ray)> {
+        let (index_of_refraction, wavelengths) = match (self.dispersion, ray.wavelengths()) {
+            // Only the hero wavelength can follow the refracted direction
+            (Some(dispersion), Some(wavelengths)) => (
+                dispersion.index_of_refraction(wavelengths.hero()), 
+                Some(wavelengths.terminate_secondary())),
+            _ => (self.index_of_refraction, ray.wavelengths()),
+        };
+
         let refraction_ratio = if hit.front_face {
-            1.0 / self.index_of_refraction
+            1.0 / index_of_refraction
         } else {
-            self.index_of_refraction
+            index_of_refraction
         };
 
         let unit_direction = ray.direction().normalized();
         let cos_theta = ((-1.0) * unit_direction).dot(hit.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let will_reflect = rng.gen::<f64>() < Self::reflectance(cos_theta, refraction_ratio);
 
@@ -95,8 +173,96 @@ impl Scatter for Dielectric {
             unit_direction.refract(hit.normal, refraction_ratio)
         };
 
-        let scattered = Ray::new(hit.p, direction);
+        let mut scattered = ray.spawn(hit.p, direction);
+        if let Some(wavelengths) = wavelengths {
+            scattered = scattered.with_wavelengths(wavelengths);
+        }
 
         Some((Color::new(1.0, 1.0, 1.0), scattered))
     }
+
+    fn albedo(&self) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Scatter for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        self.emit
+    }
+
+    fn bsdf(&self, _hit: &HitRecord, _incoming: Vec3, _scattered: Vec3) -> Option<Color> {
+        Some(Color::new(0.0, 0.0, 0.0))
+    }
+}
+
+/// Materials registered by name, so that many objects can share them.
+#[derive(Default)]
+pub struct MaterialLibrary {
+    // Materials with their IDs, from 1 in order of first registration
+    materials: HashMap<String, (Arc<dyn Scatter>, usize)>,
+    // IDs by material address
+    ids: HashMap<usize, usize>,
+}
+
+impl MaterialLibrary {
+    pub fn new() -> MaterialLibrary {
+        MaterialLibrary::default()
+    }
+
+    /// Registers `material` under `name`, replacing any previous one,
+    /// whose ID it takes over.
+    pub fn insert<S: Scatter + 'static>(&mut self, name: &str, material: S) -> Arc<dyn Scatter> {
+        let material: Arc<dyn Scatter> = Arc::new(material);
+        let id = match self.materials.get(name) {
+            Some((previous, id)) => {
+                self.ids.remove(&address(previous.as_ref()));
+                *id
+            },
+            None => self.materials.len() + 1,
+        };
+        self.materials.insert(name.to_string(), (material.clone(), id));
+        self.ids.insert(address(material.as_ref()), id);
+        material
+    }
+
+    /// Material registered under `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Scatter>> {
+        self.materials.get(name).map(|(material, _)| material)
+    }
+
+    /// ID of a registered material, from 1 in order of first registration.
+    pub fn id(&self, material: &dyn Scatter) -> Option<usize> {
+        self.ids.get(&address(material)).copied()
+    }
+}
+
+/// Address identifying a material, whatever the type of the reference to it.
+fn address(material: &dyn Scatter) -> usize {
+    material as *const dyn Scatter as *const () as usize
+}
+
+/// Lookup for hard-coded scenes, panicking on unknown names.
+/// Use `MaterialLibrary::get` for names that may be missing.
+impl Index<&str> for MaterialLibrary {
+    type Output = Arc<dyn Scatter>;
+
+    fn index(&self, name: &str) -> &Self::Output {
+        self.get(name)
+            .unwrap_or_else(|| panic!("Unknown material '{}'", name))
+    }
 }
@@ -0,0 +1,193 @@
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::random;
+use super::ray::Ray;
+use super::vec::{Color, Vec3};
+
+/// Angular distribution of the light scattered inside a medium.
+#[derive(Clone, Copy)]
+pub enum PhaseFunction {
+    Isotropic,
+    /// Henyey-Greenstein with asymmetry parameter g in (-1, 1):
+    /// positive values scatter forward, negative ones backward
+    HenyeyGreenstein(f64),
+}
+
+impl PhaseFunction {
+    /// Density of scattering light propagating along `direction` into `scattered`,
+    /// per unit solid angle.
+    pub fn value(&self, direction: Vec3, scattered: Vec3) -> f64 {
+        let cos_theta = direction.normalized().dot(scattered.normalized());
+        match *self {
+            PhaseFunction::HenyeyGreenstein(g) if g.abs() > 1.0e-3 => {
+                let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+                (1.0 - g * g) / (4.0 * std::f64::consts::PI * denom * denom.sqrt())
+            },
+            _ => 1.0 / (4.0 * std::f64::consts::PI),
+        }
+    }
+
+    /// Samples a new direction for light propagating along `direction`.
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let mut rng = random::rng();
+        let u: f64 = rng.gen();
+        let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+
+        let cos_theta = match *self {
+            PhaseFunction::HenyeyGreenstein(g) if g.abs() > 1.0e-3 => {
+                let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+                (1.0 + g * g - s * s) / (2.0 * g)
+            },
+            _ => 1.0 - 2.0 * u,
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+        let w = direction.normalized();
+        let a = if w.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = w.cross(a).normalized();
+        let u = w.cross(v);
+
+        sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w
+    }
+}
+
+/// Material of scattering events inside a medium.
+pub struct PhaseMaterial {
+    albedo: Color,
+    phase_function: PhaseFunction,
+}
+
+impl PhaseMaterial {
+    pub fn new(albedo: Color, phase_function: PhaseFunction) -> PhaseMaterial {
+        PhaseMaterial { albedo, phase_function }
+    }
+}
+
+impl Scatter for PhaseMaterial {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Color, Ray)> {
+        let direction = self.phase_function.sample(ray.direction());
+        Some((self.albedo, ray.spawn(hit.p, direction)))
+    }
+
+    fn bsdf(&self, _hit: &HitRecord, incoming: Vec3, scattered: Vec3) -> Option<Color> {
+        Some(self.phase_function.value(incoming, scattered) * self.albedo)
+    }
+
+    fn pdf(&self, _hit: &HitRecord, incoming: Vec3, scattered: Vec3) -> f64 {
+        self.phase_function.value(incoming, scattered)
+    }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
+}
+
+/// Samples the distance travelled in a homogeneous medium before scattering.
+pub fn free_flight_distance(density: f64) -> f64 {
+    let u: f64 = random::rng().gen();
+    -(1.0 - u).ln() / density
+}
+
+/// Record of a scattering event at parameter `t` along `r`.
+pub fn scattering_record<'a>(r: &Ray, t: f64, material: &'a dyn Scatter) -> HitRecord<'a> {
+    // The normal is meaningless inside a medium: face the incoming ray
+    let rec = HitRecord::new(r, t, (-1.0) * r.direction(), 0.0, 0.0, material);
+    HitRecord { medium: true, ..rec }
+}
+
+/// Homogeneous medium filling the inside of a closed boundary shape.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hit>,
+    density: f64,
+    phase_material: PhaseMaterial,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Box<dyn Hit>,
+        density: f64,
+        albedo: Color,
+        phase_function: PhaseFunction) -> ConstantMedium {
+
+        ConstantMedium {
+            boundary,
+            density,
+            phase_material: PhaseMaterial::new(albedo, phase_function),
+        }
+    }
+}
+
+impl Hit for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // Find where the ray enters and leaves the boundary
+        let enter = self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(r, enter.t + 0.0001, f64::INFINITY)?;
+
+        let t_enter = enter.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let hit_distance = free_flight_distance(self.density);
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        Some(scattering_record(r, t, &self.phase_material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Homogeneous fog filling the half-space below a given height.
+pub struct Fog {
+    density: f64,
+    height: f64,
+    phase_material: PhaseMaterial,
+}
+
+impl Fog {
+    pub fn new(density: f64, height: f64, albedo: Color, phase_function: PhaseFunction) -> Fog {
+        Fog {
+            density,
+            height,
+            phase_material: PhaseMaterial::new(albedo, phase_function),
+        }
+    }
+
+    /// Samples a scattering event along `r` before it reaches `t_max`.
+    pub fn sample_scattering(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // Parametric range of the ray below the fog height
+        let (origin_y, direction_y) = (r.origin().y(), r.direction().y());
+        let (t_enter, t_exit) = if direction_y == 0.0 {
+            if origin_y < self.height { (t_min, t_max) } else { return None; }
+        } else {
+            let t_height = (self.height - origin_y) / direction_y;
+            if direction_y > 0.0 {
+                (t_min, t_max.min(t_height))
+            } else {
+                (t_min.max(t_height), t_max)
+            }
+        };
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = r.direction().length();
+        let t = t_enter + free_flight_distance(self.density) / ray_length;
+        if t < t_exit {
+            Some(scattering_record(r, t, &self.phase_material))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,257 @@
+// Primary sample space Metropolis light transport (Kelemen et al. 2002,
+// following the formulation of Physically Based Rendering): a path is a
+// function of the random numbers it draws, so exploring the space of those
+// numbers with Markov chains explores the paths, lingering on those carrying
+// the most light. Chains draw their numbers from a sampler standing in for the
+// generator of their thread, so the camera, integrator and materials work
+// unchanged. The integrator only tells the sampler which part of the path it
+// is drawing numbers for, so that the camera subpath, the light subpath and
+// their connections each keep their numbers when another part changes length.
+//
+// The chains only give the distribution of light over the image: its
+// brightness comes from the average of independent paths of a bootstrap
+// phase, which also picks the paths the chains start from.
+
+use std::cell::RefCell;
+use std::io::{stdout, Write};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rand::Rng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use super::random::{self, Sampler, Stream};
+use super::vec::Color;
+
+/// Settings of the Markov chains of Metropolis light transport.
+#[derive(Clone, Copy)]
+pub struct Metropolis {
+    chains: u64,
+    bootstrap_samples: u64,
+    // Standard deviation of the small steps, and probability of large steps
+    sigma: f64,
+    large_step_probability: f64,
+}
+
+impl Metropolis {
+    pub fn new(chains: u64, bootstrap_samples: u64) -> Metropolis {
+        Metropolis { chains, bootstrap_samples, sigma: 0.01, large_step_probability: 0.3 }
+    }
+
+    /// Metropolis light transport whose small steps perturb the random numbers
+    /// of a path with standard deviation `sigma`, and which replaces all of
+    /// them with probability `large_step_probability`. Small steps explore
+    /// bright paths, large steps keep chains from getting stuck on them.
+    pub fn with_mutations(self, sigma: f64, large_step_probability: f64) -> Metropolis {
+        Metropolis { sigma, large_step_probability, ..self }
+    }
+
+    /// Linear colors of an image of `width` x `height` pixels, from the top
+    /// left corner, with `mutations_per_pixel` mutations per pixel on average.
+    /// `radiance` gives the light along a path through the image point (x, y),
+    /// in pixels from the top left corner, drawing its random numbers from
+    /// `random::rng`.
+    pub fn render<F>(&self, width: u64, height: u64, mutations_per_pixel: u64, seed: u64, radiance: F) -> Vec<Color>
+    where F: Fn(f64, f64) -> Color + Sync {
+
+        let black = Color::new(0.0, 0.0, 0.0);
+        let pixels = (width * height) as usize;
+
+        // A path through the image, with the pixel it goes through
+        let evaluate = |sampler: &Rc<RefCell<PrimarySampler>>| {
+            random::set_sampler(Some(Box::new(sampler.clone())));
+            let mut rng = random::rng();
+            let x = rng.gen::<f64>() * width as f64;
+            let y = rng.gen::<f64>() * height as f64;
+            let light = radiance(x, y);
+            random::set_sampler(None);
+            let pixel = (y as u64).min(height - 1) * width + (x as u64).min(width - 1);
+            (pixel as usize, light)
+        };
+
+        // Bootstrap paths, each from the first numbers of a sampler of its own
+        let bootstrap_sampler = |i: u64| {
+            Rc::new(RefCell::new(PrimarySampler::new(
+                random::generator(seed, i), self.sigma, self.large_step_probability)))
+        };
+        let weights: Vec<f64> = (0..self.bootstrap_samples)
+            .into_par_iter()
+            .map(|i| luminance(evaluate(&bootstrap_sampler(i)).1))
+            .collect();
+        let brightness = weights.iter().sum::<f64>() / self.bootstrap_samples as f64;
+        if brightness == 0.0 {
+            return vec![black; pixels];
+        }
+        let cumulative: Vec<f64> = weights.iter()
+            .scan(0.0, |sum, w| { *sum += w; Some(*sum) })
+            .collect();
+
+        let mutations = mutations_per_pixel * pixels as u64;
+        let done = AtomicU64::new(0);
+        let image = (0..self.chains)
+            .into_par_iter()
+            .fold(|| vec![black; pixels], |mut image, chain| {
+                // Start from a bootstrap path picked in proportion to its light
+                let mut rng = random::generator(seed, self.bootstrap_samples + chain);
+                let pick = rng.gen::<f64>() * cumulative[cumulative.len() - 1];
+                let start = cumulative.partition_point(|&c| c <= pick) as u64;
+                let sampler = bootstrap_sampler(start.min(self.bootstrap_samples - 1));
+                let (mut current_pixel, mut current) = evaluate(&sampler);
+
+                let first = chain * mutations / self.chains;
+                let last = (chain + 1) * mutations / self.chains;
+                for _ in first..last {
+                    sampler.borrow_mut().start_iteration();
+                    let (proposed_pixel, proposed) = evaluate(&sampler);
+
+                    // Expected values of both paths, weighted by their chance of being accepted
+                    let (current_luminance, proposed_luminance) = (luminance(current), luminance(proposed));
+                    let accept = if current_luminance > 0.0 {
+                        (proposed_luminance / current_luminance).min(1.0)
+                    } else {
+                        1.0
+                    };
+                    if accept > 0.0 {
+                        image[proposed_pixel] += proposed * (accept / proposed_luminance);
+                    }
+                    if current_luminance > 0.0 {
+                        image[current_pixel] += current * ((1.0 - accept) / current_luminance);
+                    }
+
+                    if rng.gen::<f64>() < accept {
+                        sampler.borrow_mut().accept();
+                        (current_pixel, current) = (proposed_pixel, proposed);
+                    } else {
+                        sampler.borrow_mut().reject();
+                    }
+                }
+
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                print!("\rChains: {:6} of {}", done, self.chains);
+                stdout().flush().unwrap();
+                image
+            })
+            .reduce(|| vec![black; pixels], |a, b| a.into_iter().zip(b).map(|(a, b)| a + b).collect());
+        println!("\nDone.");
+
+        let scale = brightness / mutations_per_pixel as f64;
+        image.into_iter().map(|color| color * scale).collect()
+    }
+}
+
+/// Luminance of a linear Rec. 709 color, the light the chains follow.
+fn luminance(color: Color) -> f64 {
+    let luminance = 0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z();
+    if luminance.is_finite() { luminance.max(0.0) } else { 0.0 }
+}
+
+/// Random number of a path in primary sample space.
+#[derive(Clone, Copy, Default)]
+struct PrimarySample {
+    value: f64,
+    // Iteration of the last change, and the values before it in case it is rejected
+    modified: u64,
+    backup_value: f64,
+    backup_modified: u64,
+}
+
+/// Random numbers of the current path of a chain, mutated lazily as they are
+/// drawn: a number that was not drawn for a few iterations gets all the small
+/// steps it missed at once. The numbers of the streams are interleaved.
+struct PrimarySampler {
+    rng: Xoshiro256PlusPlus,
+    samples: Vec<PrimarySample>,
+    stream: usize,
+    // Index of the next number within the stream
+    next: usize,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    sigma: f64,
+    large_step_probability: f64,
+}
+
+impl PrimarySampler {
+    /// Sampler whose first path has all its numbers fresh from `rng`.
+    fn new(rng: Xoshiro256PlusPlus, sigma: f64, large_step_probability: f64) -> PrimarySampler {
+        PrimarySampler {
+            rng,
+            samples: Vec::new(),
+            stream: Stream::Camera as usize,
+            next: 0,
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            sigma,
+            large_step_probability,
+        }
+    }
+
+    /// Starts the proposal of a mutated path.
+    fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < self.large_step_probability;
+        self.start_stream(Stream::Camera);
+    }
+
+    fn start_stream(&mut self, stream: Stream) {
+        self.stream = stream as usize;
+        self.next = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    /// Goes back to the numbers of the path before the last iteration.
+    fn reject(&mut self) {
+        for sample in self.samples.iter_mut().filter(|s| s.modified == self.iteration) {
+            sample.value = sample.backup_value;
+            sample.modified = sample.backup_modified;
+        }
+        self.iteration -= 1;
+    }
+}
+
+impl Sampler for Rc<RefCell<PrimarySampler>> {
+    fn next(&mut self) -> f64 {
+        let mut sampler = self.borrow_mut();
+        let sampler = &mut *sampler;
+        let index = sampler.next * Stream::COUNT + sampler.stream;
+        while sampler.samples.len() <= index {
+            // Numbers never drawn before are fresh, as if from the last large step
+            let value = sampler.rng.gen();
+            sampler.samples.push(PrimarySample { value, modified: sampler.last_large_step, ..Default::default() });
+        }
+        let sample = &mut sampler.samples[index];
+        sampler.next += 1;
+
+        // Numbers not drawn since the last large step start from a fresh value
+        if sample.modified < sampler.last_large_step {
+            sample.value = sampler.rng.gen();
+            sample.modified = sampler.last_large_step;
+        }
+        sample.backup_value = sample.value;
+        sample.backup_modified = sample.modified;
+
+        if sampler.large_step {
+            sample.value = sampler.rng.gen();
+        } else {
+            // Gaussian step (Box-Muller), as large as all the steps missed
+            let (u1, u2): (f64, f64) = (sampler.rng.gen(), sampler.rng.gen());
+            let normal = (-2.0 * (1.0 - u1).ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+            let steps = (sampler.iteration - sample.modified) as f64;
+            sample.value += normal * sampler.sigma * steps.sqrt();
+            sample.value = (sample.value - sample.value.floor()).min(1.0 - f64::EPSILON);
+        }
+        sample.modified = sampler.iteration;
+        sample.value
+    }
+
+    fn start_stream(&mut self, stream: Stream) {
+        self.borrow_mut().start_stream(stream);
+    }
+}
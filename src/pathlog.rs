@@ -0,0 +1,158 @@
+use std::fmt::Write;
+
+use clap::ValueEnum;
+
+use super::hit::HitRecord;
+use super::ray::Ray;
+use super::vec::{Color, Point3, Vec3};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Ray traced along a path, with what it hit and the light it brought back.
+pub struct Bounce {
+    pub origin: Point3,
+    pub direction: Vec3,
+    pub hit: Option<SurfaceHit>,
+    pub emitted: Color,
+    // Attenuation of the scattered ray, weighted for Russian roulette,
+    // `None` if the ray was absorbed or escaped
+    pub attenuation: Option<Color>,
+    pub color: Color,
+}
+
+pub struct SurfaceHit {
+    pub object_id: usize,
+    pub t: f64,
+    pub point: Point3,
+    pub normal: Vec3,
+    pub front_face: bool,
+    pub material: &'static str,
+}
+
+impl Bounce {
+    pub fn new(r: &Ray) -> Bounce {
+        let black = Color::new(0.0, 0.0, 0.0);
+        Bounce {
+            origin: r.origin(),
+            direction: r.direction(),
+            hit: None,
+            emitted: black,
+            attenuation: None,
+            color: black,
+        }
+    }
+
+    pub fn set_hit(&mut self, rec: &HitRecord) {
+        let material = rec.material.name();
+        self.hit = Some(SurfaceHit {
+            object_id: rec.object_id,
+            t: rec.t,
+            point: rec.p,
+            normal: rec.normal,
+            front_face: rec.front_face,
+            material: material.rsplit("::").next().unwrap_or(material),
+        });
+    }
+}
+
+/// Sets the light returned along each ray of a path, gathered back from its end.
+pub fn gather(bounces: &mut [Bounce]) {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let mut color = black;
+    for entry in bounces.iter_mut().rev() {
+        color = entry.emitted + entry.attenuation.map_or(black, |a| a * color);
+        entry.color = color;
+    }
+}
+
+/// Camera sample of a pixel, with the rays of its path.
+pub struct SampleLog {
+    pub u: f64,
+    pub v: f64,
+    pub color: Color,
+    pub bounces: Vec<Bounce>,
+}
+
+impl SampleLog {
+    pub fn new(u: f64, v: f64) -> SampleLog {
+        SampleLog { u, v, color: Color::new(0.0, 0.0, 0.0), bounces: Vec::new() }
+    }
+}
+
+/// Report of the samples of pixel (x, y), counted from the top left corner.
+pub fn format(format: LogFormat, (x, y): (u64, u64), color: Color, samples: &[SampleLog]) -> String {
+    match format {
+        LogFormat::Text => text(x, y, color, samples),
+        LogFormat::Json => json(x, y, color, samples),
+    }
+}
+
+fn text(x: u64, y: u64, color: Color, samples: &[SampleLog]) -> String {
+    let mut out = String::new();
+    writeln!(out, "Pixel {},{}: {} samples, color {}", x, y, samples.len(), color).unwrap();
+    for (n, sample) in samples.iter().enumerate() {
+        writeln!(out, "Sample {} at ({:.4}, {:.4}): color {}", n, sample.u, sample.v, sample.color).unwrap();
+        if sample.bounces.is_empty() {
+            writeln!(out, "  no camera ray").unwrap();
+        }
+        for (depth, bounce) in sample.bounces.iter().enumerate() {
+            writeln!(out, "  {} ray from {} towards {}", depth, bounce.origin, bounce.direction).unwrap();
+            match &bounce.hit {
+                Some(hit) => {
+                    let side = if hit.front_face { "front" } else { "back" };
+                    writeln!(out, "    hit object {} at t = {} ({} face of {}), point {}, normal {}",
+                        hit.object_id, hit.t, side, hit.material, hit.point, hit.normal).unwrap();
+                },
+                None => writeln!(out, "    missed").unwrap(),
+            }
+            match bounce.attenuation {
+                Some(attenuation) => writeln!(out, "    emitted {}, attenuation {}, color {}",
+                    bounce.emitted, attenuation, bounce.color).unwrap(),
+                None => {
+                    let end = if bounce.hit.is_some() { "absorbed" } else { "escaped" };
+                    writeln!(out, "    emitted {}, {}, color {}", bounce.emitted, end, bounce.color).unwrap()
+                },
+            }
+        }
+    }
+    out
+}
+
+fn json(x: u64, y: u64, color: Color, samples: &[SampleLog]) -> String {
+    let samples: Vec<String> = samples.iter().map(|sample| {
+        let bounces: Vec<String> = sample.bounces.iter().map(|bounce| {
+            let hit = match &bounce.hit {
+                Some(hit) => format!(
+                    r#"{{"object": {}, "t": {}, "point": {}, "normal": {}, "front_face": {}, "material": "{}"}}"#,
+                    hit.object_id, number(hit.t), vector(hit.point), vector(hit.normal), hit.front_face, hit.material),
+                None => "null".to_string(),
+            };
+            format!(
+                r#"{{"origin": {}, "direction": {}, "hit": {}, "emitted": {}, "attenuation": {}, "color": {}}}"#,
+                vector(bounce.origin), vector(bounce.direction), hit, vector(bounce.emitted),
+                bounce.attenuation.map_or("null".to_string(), vector), vector(bounce.color))
+        }).collect();
+        format!(r#"{{"u": {}, "v": {}, "color": {}, "rays": [{}]}}"#,
+            number(sample.u), number(sample.v), vector(sample.color), bounces.join(", "))
+    }).collect();
+
+    format!("{{\"pixel\": [{}, {}], \"color\": {}, \"samples\": [\n  {}\n]}}\n",
+        x, y, vector(color), samples.join(",\n  "))
+}
+
+/// JSON number, or `null` for NaN and infinities that JSON cannot represent.
+fn number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn vector(v: Vec3) -> String {
+    format!("[{}, {}, {}]", number(v.x()), number(v.y()), number(v.z()))
+}
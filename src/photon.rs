@@ -0,0 +1,405 @@
+// Photon mapping (Jensen 1996): photons are traced from the lights before
+// rendering and stored where they land on diffuse surfaces, in a caustic map
+// for photons that only went through specular bounces and in a global map for
+// all of them. Camera paths then follow specular bounces up to a diffuse
+// surface, where they add direct light sampled on the lights, caustics
+// estimated from the density of nearby caustic photons, and indirect light
+// from a final gather ray looking up the global map where it lands.
+//
+// Photons are traced in RGB, so caustics of dispersive glass are not spread
+// into colors.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use super::aov::AovSample;
+use super::hit::{Hit, HitRecord};
+use super::integrator::{self, Integrator};
+use super::pathlog::{self, Bounce};
+use super::random;
+use super::ray::Ray;
+use super::scene::Scene;
+use super::spectrum;
+use super::vec::{Color, Point3, Vec3};
+
+/// Light arriving at a diffuse surface.
+#[derive(Clone, Copy)]
+pub struct Photon {
+    p: Point3,
+    // Direction the light propagates in
+    direction: Vec3,
+    power: Color,
+    // Splitting axis when used as a node of the kd-tree
+    axis: usize,
+}
+
+/// Photons in a balanced kd-tree, each node being the median of its subtree
+/// along the axis where the subtree is widest.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+}
+
+/// Photon found at squared distance `distance_squared`, ordered by distance
+/// so that the heap of the nearest photons has the farthest one on top.
+struct Neighbor<'a> {
+    distance_squared: f64,
+    photon: &'a Photon,
+}
+
+impl PartialEq for Neighbor<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance_squared == other.distance_squared
+    }
+}
+
+impl Eq for Neighbor<'_> {}
+
+impl PartialOrd for Neighbor<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbor<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_squared.total_cmp(&other.distance_squared)
+    }
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> PhotonMap {
+        balance(&mut photons);
+        PhotonMap { photons }
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    /// The `count` photons nearest to `p` within `max_radius`, with the squared
+    /// radius of the sphere containing them.
+    fn nearest(&self, p: Point3, count: usize, max_radius: f64) -> (Vec<&Photon>, f64) {
+        let mut heap = BinaryHeap::with_capacity(count + 1);
+        let mut radius_squared = max_radius * max_radius;
+        self.search(&self.photons, p, count, &mut radius_squared, &mut heap);
+
+        // With fewer photons than wanted, the density is over the whole search radius
+        let radius_squared = match heap.peek() {
+            Some(_) if heap.len() < count && max_radius.is_finite() => max_radius * max_radius,
+            Some(farthest) => farthest.distance_squared,
+            None => 0.0,
+        };
+        (heap.into_iter().map(|n| n.photon).collect(), radius_squared)
+    }
+
+    fn search<'a>(
+        &self,
+        nodes: &'a [Photon],
+        p: Point3,
+        count: usize,
+        radius_squared: &mut f64,
+        heap: &mut BinaryHeap<Neighbor<'a>>) {
+
+        if nodes.is_empty() {
+            return;
+        }
+        let mid = nodes.len() / 2;
+        let node = &nodes[mid];
+        let (below, above) = (&nodes[..mid], &nodes[mid + 1..]);
+
+        // Nearer side first, the other one only if the splitting plane is close enough
+        let offset = p[node.axis] - node.p[node.axis];
+        let (near, far) = if offset < 0.0 { (below, above) } else { (above, below) };
+        self.search(near, p, count, radius_squared, heap);
+
+        let w = node.p - p;
+        let distance_squared = w.dot(w);
+        if distance_squared < *radius_squared {
+            heap.push(Neighbor { distance_squared, photon: node });
+            if heap.len() > count {
+                heap.pop();
+            }
+            if heap.len() == count {
+                *radius_squared = heap.peek().unwrap().distance_squared;
+            }
+        }
+
+        if offset * offset < *radius_squared {
+            self.search(far, p, count, radius_squared, heap);
+        }
+    }
+}
+
+/// Arranges `photons` as an implicit kd-tree: the median of each range is its
+/// root, with the photons below it on the left and those above on the right.
+fn balance(photons: &mut [Photon]) {
+    if photons.is_empty() {
+        return;
+    }
+
+    let (mut min, mut max) = (photons[0].p, photons[0].p);
+    for photon in photons.iter() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(photon.p[axis]);
+            max[axis] = max[axis].max(photon.p[axis]);
+        }
+    }
+    let extent = max - min;
+    let axis = (0..3).max_by(|&a, &b| extent[a].total_cmp(&extent[b])).unwrap();
+
+    let mid = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| a.p[axis].total_cmp(&b.p[axis]));
+    photons[mid].axis = axis;
+    let (below, rest) = photons.split_at_mut(mid);
+    balance(below);
+    balance(&mut rest[1..]);
+}
+
+/// Photon mapper with a caustic map and a global map.
+pub struct PhotonMapper {
+    max_depth: u64,
+    caustic_map: PhotonMap,
+    global_map: PhotonMap,
+    // Photons used by each radiance estimate, and the largest radius they are looked up in
+    neighbors: usize,
+    max_radius: f64,
+}
+
+impl PhotonMapper {
+    /// Photon mapper tracing photons from the lights of `scene` until
+    /// `global_photons` and `caustic_photons` are stored in each map.
+    pub fn new(scene: &Scene, max_depth: u64, global_photons: usize, caustic_photons: usize, seed: u64) -> PhotonMapper {
+        random::seed_stream(seed, 0);
+        let global_map = trace_photons(scene, max_depth, global_photons, false);
+        random::seed_stream(seed, 1);
+        let caustic_map = trace_photons(scene, max_depth, caustic_photons, true);
+
+        PhotonMapper {
+            max_depth,
+            caustic_map,
+            global_map,
+            neighbors: 50,
+            max_radius: f64::INFINITY,
+        }
+    }
+
+    /// Photon mapper estimating radiance from the `neighbors` photons nearest
+    /// to each point, within `max_radius`. Fewer photons give sharper but
+    /// noisier caustics, and a smaller radius keeps light from leaking
+    /// across nearby surfaces.
+    pub fn with_density_estimation(self, neighbors: usize, max_radius: f64) -> PhotonMapper {
+        PhotonMapper { neighbors, max_radius, ..self }
+    }
+
+    pub fn photon_counts(&self) -> (usize, usize) {
+        (self.global_map.len(), self.caustic_map.len())
+    }
+
+    /// Radiance leaving `rec` towards the start of `r`, estimated from the photons of `map`.
+    fn estimate(&self, map: &PhotonMap, r: &Ray, rec: &HitRecord) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let (photons, radius_squared) = map.nearest(rec.p, self.neighbors, self.max_radius);
+        if radius_squared == 0.0 {
+            return black;
+        }
+
+        let outgoing = -1.0 * r.direction();
+        let flux = photons.iter()
+            .filter_map(|photon| rec.material.bsdf(rec, photon.direction, outgoing).map(|f| f * photon.power))
+            .fold(black, |sum, light| sum + light);
+        spectrum::values(r.wavelengths(), flux / (PI * radius_squared))
+    }
+
+    /// Light reaching `rec` straight from a random point of a random light,
+    /// and scattered towards the start of `r`.
+    fn direct(&self, scene: &Scene, r: &Ray, rec: &HitRecord) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        if scene.lights.is_empty() {
+            return black;
+        }
+
+        let light = &scene.lights[random::rng().gen_range(0..scene.lights.len())];
+        let sample = light.sample();
+        let w = sample.p - rec.p;
+        let distance = w.length();
+        let direction = w / distance;
+        let f = match rec.material.bsdf(rec, -1.0 * direction, -1.0 * r.direction()) {
+            Some(f) => f,
+            None => return black,
+        };
+        let g = direction.dot(rec.normal).abs() * direction.dot(sample.normal).abs() / (distance * distance);
+        if f.near_zero() || g == 0.0 {
+            return black;
+        }
+
+        let shadow = r.spawn(rec.p, direction);
+        let transmittance = scene.world.transmittance(&shadow, 0.001, distance - 0.001);
+        if transmittance == 0.0 {
+            return black;
+        }
+        let emitted = sample.material.emitted(sample.u, sample.v, sample.p);
+        let pdf = 1.0 / (scene.lights.len() as f64 * light.area());
+        spectrum::values(r.wavelengths(), f * emitted) * (g * transmittance / pdf)
+    }
+}
+
+/// Whether `rec` lies on a light that is sampled directly.
+fn is_light(scene: &Scene, rec: &HitRecord) -> bool {
+    scene.lights.iter().any(|light| light.object_id() == rec.object_id)
+}
+
+/// Whether the material at `rec` scatters diffusely, so that it can store photons.
+/// Emitters have a (black) BSDF but reflect nothing, so they store none.
+fn is_diffuse(rec: &HitRecord, direction: Vec3) -> bool {
+    let emitter = !rec.material.emitted(rec.u, rec.v, rec.p).near_zero();
+    !rec.medium && !emitter && rec.material.bsdf(rec, direction, direction).is_some()
+}
+
+/// Traces photons from the lights of `scene` until at least `count` of them are stored,
+/// only those that reached a diffuse surface through specular bounces alone
+/// if `caustic`.
+fn trace_photons(scene: &Scene, max_depth: u64, count: usize, caustic: bool) -> PhotonMap {
+    let mut photons = Vec::with_capacity(count);
+    if scene.lights.is_empty() || count == 0 {
+        return PhotonMap::new(photons);
+    }
+
+    // Give up on maps that photons never or hardly ever reach, e.g. caustic
+    // maps without specular objects
+    let max_emitted = 100 * count;
+    let mut rng = random::rng();
+    let mut emitted = 0;
+    while photons.len() < count && emitted < max_emitted && !(photons.is_empty() && emitted >= count) {
+        emitted += 1;
+        let light = &scene.lights[rng.gen_range(0..scene.lights.len())];
+        let (sample, direction, pdf_direction) = light.sample_emission();
+        if pdf_direction == 0.0 {
+            continue;
+        }
+        let pdf = 1.0 / (scene.lights.len() as f64 * light.area());
+        let emitted_light = sample.material.emitted(sample.u, sample.v, sample.p);
+        let mut power = emitted_light * (direction.dot(sample.normal).abs() / (pdf * pdf_direction));
+        let mut ray = Ray::new(sample.p, direction);
+
+        let mut specular = false;
+        for bounce in 0..max_depth {
+            let rec = match scene.world.hit(&ray, 0.001, f64::INFINITY) {
+                Some(rec) => rec,
+                None => break,
+            };
+
+            if is_diffuse(&rec, ray.direction()) {
+                if !caustic || specular {
+                    photons.push(Photon { p: rec.p, direction: ray.direction(), power, axis: 0 });
+                }
+                if caustic {
+                    break;
+                }
+                specular = false;
+            } else {
+                specular = !rec.medium;
+            }
+
+            let (color, scattered) = match rec.material.scatter(&ray, &rec) {
+                Some(scatter) => scatter,
+                None => break,
+            };
+
+            // Russian roulette keeping the power of the surviving photons
+            let survival = if bounce == 0 { 1.0 } else { color.x().max(color.y()).max(color.z()).min(1.0) };
+            if survival < 1.0 && rng.gen::<f64>() >= survival {
+                break;
+            }
+            power = power * color / survival;
+            ray = scattered;
+        }
+    }
+
+    for photon in photons.iter_mut() {
+        photon.power /= emitted as f64;
+    }
+    PhotonMap::new(photons)
+}
+
+impl Integrator for PhotonMapper {
+    fn radiance(&self, r: &Ray, scene: &Scene, aov: &mut AovSample, mut log: Option<&mut Vec<Bounce>>) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let first = log.as_ref().map_or(0, |log| log.len());
+
+        let mut radiance = black;
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = None;
+        // Whether the path went through a diffuse surface, and is now a final gather ray
+        let mut gathering = false;
+        for bounce in 0..self.max_depth {
+            let r: &Ray = ray.as_ref().unwrap_or(r);
+            if let Some(log) = log.as_deref_mut() {
+                log.push(Bounce::new(r));
+            }
+
+            let rec = match scene.world.hit(r, 0.001, f64::INFINITY) {
+                Some(rec) => rec,
+                None => {
+                    let background = spectrum::radiance(r, scene.background.color(r));
+                    integrator::add_light(aov, bounce, throughput * background);
+                    radiance += throughput * background;
+                    if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                        entry.emitted = background;
+                    }
+                    break;
+                },
+            };
+
+            if bounce == 0 {
+                aov.record_first_hit(&rec, r, scene);
+            }
+            if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                entry.set_hit(&rec);
+            }
+
+            // Light from the lights after a diffuse surface is in the estimates made there
+            let mut emitted = black;
+            if !(gathering && is_light(scene, &rec)) {
+                emitted = spectrum::radiance(r, rec.material.emitted(rec.u, rec.v, rec.p));
+            }
+
+            if is_diffuse(&rec, r.direction()) {
+                if gathering {
+                    emitted += self.estimate(&self.global_map, r, &rec);
+                } else {
+                    emitted += self.direct(scene, r, &rec) + self.estimate(&self.caustic_map, r, &rec);
+                }
+            }
+            integrator::add_light(aov, bounce, throughput * emitted);
+            radiance += throughput * emitted;
+            if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                entry.emitted = emitted;
+            }
+
+            // Final gather rays end at the diffuse surface they reach
+            if gathering && is_diffuse(&rec, r.direction()) {
+                break;
+            }
+            gathering = gathering || is_diffuse(&rec, r.direction());
+
+            let (color, scattered) = match rec.material.scatter(r, &rec) {
+                Some(scatter) => scatter,
+                None => break,
+            };
+            let reflectance = spectrum::reflectance(r, &scattered, color);
+            throughput = throughput * reflectance;
+            if let Some(entry) = log.as_deref_mut().and_then(|log| log.last_mut()) {
+                entry.attenuation = Some(reflectance);
+            }
+            ray = Some(scattered);
+        }
+
+        if let Some(log) = log {
+            pathlog::gather(&mut log[first..]);
+        }
+        radiance
+    }
+}
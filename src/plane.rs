@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Infinite plane through `point`, facing `normal`.
+pub struct Plane {
+    point: Point3,
+    normal: Vec3,
+    // Orthonormal axes in the plane, for texture coordinates
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Arc<dyn Scatter>,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, material: Arc<dyn Scatter>) -> Plane {
+        let normal = normal.normalized();
+        let a = if normal.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let bitangent = normal.cross(a).normalized();
+        let tangent = bitangent.cross(normal);
+
+        Plane { point, normal, tangent, bitangent, material }
+    }
+}
+
+impl Hit for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1.0e-8 {
+            return None;
+        }
+
+        let t = self.normal.dot(self.point - r.origin()) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        // Texture coordinates are the (unbounded) distances along the plane axes
+        let planar = r.at(t) - self.point;
+        let u = planar.dot(self.tangent);
+        let v = planar.dot(self.bitangent);
+
+        Some(HitRecord::new(r, t, self.normal, u, v, self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
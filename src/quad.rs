@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::random;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Parallelogram with a corner at `q` and sides `u` and `v`.
+#[derive(Clone)]
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    // Plane containing the quad: normal . p = d
+    normal: Vec3,
+    d: f64,
+    // Scaled normal used to find the planar coordinates of hit points
+    w: Vec3,
+    material: Arc<dyn Scatter>,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Scatter>) -> Quad {
+        let n = u.cross(v);
+        let normal = n.normalized();
+        let d = normal.dot(q);
+        let w = n / n.dot(n);
+
+        Quad { q, u, v, normal, d, w, material }
+    }
+
+    /// Rectangle [x0, x1] x [y0, y1] in the plane z = k.
+    pub fn xy_rect(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: Arc<dyn Scatter>) -> Quad {
+        Quad::new(
+            Point3::new(x0, y0, k),
+            Vec3::new(x1 - x0, 0.0, 0.0),
+            Vec3::new(0.0, y1 - y0, 0.0),
+            material)
+    }
+
+    /// Rectangle [x0, x1] x [z0, z1] in the plane y = k.
+    pub fn xz_rect(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Scatter>) -> Quad {
+        Quad::new(
+            Point3::new(x0, k, z0),
+            Vec3::new(0.0, 0.0, z1 - z0),
+            Vec3::new(x1 - x0, 0.0, 0.0),
+            material)
+    }
+
+    /// Rectangle [y0, y1] x [z0, z1] in the plane x = k.
+    pub fn yz_rect(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Scatter>) -> Quad {
+        Quad::new(
+            Point3::new(k, y0, z0),
+            Vec3::new(0.0, y1 - y0, 0.0),
+            Vec3::new(0.0, 0.0, z1 - z0),
+            material)
+    }
+
+    pub fn area(&self) -> f64 {
+        self.u.cross(self.v).length()
+    }
+
+    /// Uniformly distributed point on the quad, with its planar coordinates.
+    pub fn sample(&self) -> (Point3, f64, f64) {
+        let mut rng = random::rng();
+        let (alpha, beta): (f64, f64) = (rng.gen(), rng.gen());
+        (self.q + alpha * self.u + beta * self.v, alpha, beta)
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    pub fn material(&self) -> &dyn Scatter {
+        self.material.as_ref()
+    }
+}
+
+impl Hit for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1.0e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(r.origin())) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        // Planar coordinates of the hit point along the sides
+        let planar = r.at(t) - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(HitRecord::new(r, t, self.normal, alpha, beta, self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let diagonal1 = Aabb::from_points(self.q, self.q + self.u + self.v);
+        let diagonal2 = Aabb::from_points(self.q + self.u, self.q + self.v);
+        Some(Aabb::surrounding(diagonal1, diagonal2).padded(1.0e-4))
+    }
+}
@@ -0,0 +1,117 @@
+// Random numbers for rendering come from a per-thread generator that is
+// reseeded at the start of every pixel from the pixel coordinates, so that
+// each pixel gets the same samples whatever the order (or subset) of pixels
+// rendered, and whichever thread renders it. A sampler can also stand in for
+// the generator, e.g. to replay and mutate the numbers of a path for
+// Metropolis light transport.
+
+use std::cell::RefCell;
+
+use rand::{RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+/// Source of the random numbers of the current thread in place of its generator.
+pub trait Sampler {
+    /// Next number, in [0, 1).
+    fn next(&mut self) -> f64;
+
+    /// Draws the next numbers from the start of `stream`.
+    fn start_stream(&mut self, _stream: Stream) {}
+}
+
+/// Parts of a bidirectional path whose random numbers a sampler can keep
+/// apart, so that a part drawing more or fewer numbers than before leaves
+/// the numbers of the others in place.
+#[derive(Clone, Copy)]
+pub enum Stream {
+    Camera,
+    Light,
+    Connection,
+}
+
+impl Stream {
+    pub const COUNT: usize = 3;
+}
+
+thread_local! {
+    static GENERATOR: RefCell<Xoshiro256PlusPlus> = RefCell::new(Xoshiro256PlusPlus::seed_from_u64(0));
+    static SAMPLER: RefCell<Option<Box<dyn Sampler>>> = RefCell::new(None);
+}
+
+/// Handle to the generator of the current thread.
+pub struct LocalRng;
+
+impl RngCore for LocalRng {
+    fn next_u32(&mut self) -> u32 {
+        // Numbers in [0, 1) scaled to the full range of integers, so that
+        // floats drawn from them come back unchanged
+        match SAMPLER.with(|s| s.borrow_mut().as_mut().map(|s| s.next())) {
+            Some(x) => (x * 4294967296.0) as u32,
+            None => GENERATOR.with(|g| g.borrow_mut().next_u32()),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match SAMPLER.with(|s| s.borrow_mut().as_mut().map(|s| s.next())) {
+            Some(x) => ((x * 9007199254740992.0) as u64) << 11,
+            None => GENERATOR.with(|g| g.borrow_mut().next_u64()),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+pub fn rng() -> LocalRng {
+    LocalRng
+}
+
+/// Restarts the generator of the current thread for pixel (i, j).
+pub fn seed_pixel(seed: u64, i: u64, j: u64) {
+    let key = mix(mix(mix(seed) ^ i) ^ j);
+    GENERATOR.with(|g| *g.borrow_mut() = Xoshiro256PlusPlus::seed_from_u64(key));
+}
+
+/// Restarts the generator of the current thread for work done before
+/// rendering pixels (e.g. tracing photons), numbered by `stream`.
+pub fn seed_stream(seed: u64, stream: u64) {
+    GENERATOR.with(|g| *g.borrow_mut() = generator(seed, stream));
+}
+
+/// Generator independent of those of the pixels, numbered by `stream`.
+pub fn generator(seed: u64, stream: u64) -> Xoshiro256PlusPlus {
+    Xoshiro256PlusPlus::seed_from_u64(mix(mix(!seed) ^ stream))
+}
+
+/// Draws the random numbers of the current thread from `sampler`,
+/// or from its generator again if `None`.
+pub fn set_sampler(sampler: Option<Box<dyn Sampler>>) {
+    SAMPLER.with(|s| *s.borrow_mut() = sampler);
+}
+
+/// Draws the next numbers of the current thread from the start of `stream`,
+/// if they come from a sampler.
+pub fn start_stream(stream: Stream) {
+    SAMPLER.with(|s| {
+        if let Some(sampler) = s.borrow_mut().as_mut() {
+            sampler.start_stream(stream);
+        }
+    });
+}
+
+/// SplitMix64 finalizer, spreading nearby keys far apart.
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
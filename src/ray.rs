@@ -1,15 +1,39 @@
+use crate::spectrum::Wavelengths;
 use crate::vec::{Point3, Vec3};
 
 pub struct Ray {
     origin: Point3,
-    direction: Vec3
+    direction: Vec3,
+    time: f64,
+    wavelengths: Option<Wavelengths>,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Ray {
         Ray {
             origin,
-            direction
+            direction,
+            time: 0.0,
+            wavelengths: None,
+        }
+    }
+
+    pub fn with_time(self, time: f64) -> Ray {
+        Ray { time, ..self }
+    }
+
+    pub fn with_wavelengths(self, wavelengths: Wavelengths) -> Ray {
+        Ray { wavelengths: Some(wavelengths), ..self }
+    }
+
+    /// Spawns a new ray (e.g. a scattered one) at the same time
+    /// and carrying the same wavelengths.
+    pub fn spawn(&self, origin: Point3, direction: Vec3) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time: self.time,
+            wavelengths: self.wavelengths,
         }
     }
 
@@ -21,6 +45,14 @@ impl Ray {
         self.direction
     }
 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn wavelengths(&self) -> Option<Wavelengths> {
+        self.wavelengths
+    }
+
     pub fn at(&self, t: f64) -> Point3 {
         self.origin + t * self.direction
     }
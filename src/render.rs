@@ -1,107 +1,375 @@
-use std::io::{stdout, Write};
-use std::path::Path;
+use std::fmt::Display;
+use std::io::{self, stdout, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use image::ImageError;
 use rand::Rng;
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
+use super::aov::{Aov, AovAccumulator, AovSample, Layers};
 use super::camera::Camera;
-use super::hit::{Hit, World};
-use super::ray::Ray;
+use super::debug::{self, DebugView};
+use super::denoise;
+use super::integrator::Integrator;
+use super::mlt::Metropolis;
+use super::pathlog::{Bounce, SampleLog};
+use super::random;
+use super::scene::Scene;
 use super::size::Size;
+use super::spectrum::Wavelengths;
 use super::vec::Color;
 
 pub struct Render {
-    world: World,
-    camera: Camera,
+    scene: Scene,
+    camera: Box<dyn Camera>,
+    integrator: Box<dyn Integrator>,
     samples_per_pixel: u64,
-    max_depth: u64,
     image_size: Size,
+    spectral: bool,
+    random_seed: u64,
+    // Pixels rendered, and whether to write them in a black full frame
+    crop: Option<[u64; 4]>,
+    full_frame: bool,
+    aovs: Vec<Aov>,
+    denoise: bool,
+    // Diagnostic view, with the maximum depth of paths
+    debug_view: Option<(DebugView, u64)>,
+    // Radiance limit of the light reaching the first hit after more than one bounce
+    clamp_indirect: Option<f64>,
+    // Samples with NaN or infinite values, discarded as black
+    invalid_samples: AtomicU64,
+    log_invalid_samples: bool,
+    max_invalid_fraction: Option<f64>,
+    // Markov chains replacing the samples of each pixel
+    metropolis: Option<Metropolis>,
+}
+
+/// Reason for a render to fail.
+pub enum RenderError {
+    /// More samples than allowed were NaN or infinite.
+    InvalidSamples { count: u64, total: u64 },
+    Image(ImageError),
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::InvalidSamples { count, total } =>
+                write!(f, "{} of {} samples were NaN or infinite", count, total),
+            RenderError::Image(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<ImageError> for RenderError {
+    fn from(e: ImageError) -> RenderError {
+        RenderError::Image(e)
+    }
 }
 
 impl Render {
     pub fn new(
-        world: World,
-        camera: Camera,
+        scene: Scene,
+        camera: Box<dyn Camera>,
+        integrator: Box<dyn Integrator>,
         samples_per_pixel: u64,
-        max_depth: u64,
-        image_size: Size) -> Render {
+        image_size: Size,
+        spectral: bool) -> Render {
         
-        Render { world, camera, samples_per_pixel, max_depth, image_size }
+        Render {
+            scene,
+            camera,
+            integrator,
+            samples_per_pixel,
+            image_size,
+            spectral,
+            random_seed: 0,
+            crop: None,
+            full_frame: false,
+            aovs: Vec::new(),
+            denoise: false,
+            debug_view: None,
+            clamp_indirect: None,
+            invalid_samples: AtomicU64::new(0),
+            log_invalid_samples: false,
+            max_invalid_fraction: None,
+            metropolis: None,
+        }
+    }
+
+    /// Render whose random samples derive from `random_seed`, giving
+    /// each pixel the same result in any render with the same seed.
+    pub fn with_seed(self, random_seed: u64) -> Render {
+        Render { random_seed, ..self }
+    }
+
+    /// Render of the pixels [x0, x1) x [y0, y1), counted from the top left
+    /// corner, either written alone or within a black full frame.
+    pub fn with_crop(self, pixels: [u64; 4], full_frame: bool) -> Render {
+        Render { crop: Some(pixels), full_frame, ..self }
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    /// Render writing the given passes next to the image: as layers of the
+    /// same file for OpenEXR images, and as separate OpenEXR files otherwise.
+    pub fn with_aovs(self, aovs: Vec<Aov>) -> Render {
+        Render { aovs, ..self }
+    }
+
+    /// Render whose image is denoised, guided by its albedo, normal and depth.
+    pub fn with_denoise(self, denoise: bool) -> Render {
+        Render { denoise, ..self }
+    }
+
+    /// Render shading pixels with a diagnostic view instead of the integrator,
+    /// following paths up to `max_depth` bounces.
+    pub fn with_debug_view(self, debug_view: Option<DebugView>, max_depth: u64) -> Render {
+        Render { debug_view: debug_view.map(|view| (view, max_depth)), ..self }
+    }
+
+    /// Render limiting the radiance of indirect light in each sample, trading
+    /// bias for fewer fireflies.
+    pub fn with_clamp_indirect(self, clamp_indirect: Option<f64>) -> Render {
+        Render { clamp_indirect, ..self }
+    }
+
+    /// Render reporting each NaN or infinite sample, and failing if their
+    /// fraction of all samples exceeds `max_invalid_fraction`.
+    pub fn with_invalid_samples(self, log: bool, max_invalid_fraction: Option<f64>) -> Render {
+        Render { log_invalid_samples: log, max_invalid_fraction, ..self }
+    }
+
+    /// Render by Metropolis light transport, with as many mutations per
+    /// pixel as samples per pixel otherwise.
+    pub fn with_metropolis(self, metropolis: Option<Metropolis>) -> Render {
+        Render { metropolis, ..self }
+    }
+
+    /// NaN or infinite samples discarded in the last image rendered.
+    pub fn invalid_samples(&self) -> u64 {
+        self.invalid_samples.load(Ordering::Relaxed)
+    }
+
+    pub fn set_camera(&mut self, camera: Box<dyn Camera>) {
+        self.camera = camera;
     }
 
-    pub fn pixel_color(&self, i: u64, j: u64) -> Color { 
+    /// Linear color of pixel (i, j), counted from the bottom left corner,
+    /// with the average of its passes.
+    pub fn pixel_color(&self, i: u64, j: u64) -> (Color, AovSample) { 
+        self.sample_pixel(i, j, None)
+    }
+
+    /// Linear color of pixel (i, j), with the rays traced for each of its samples.
+    pub fn trace_pixel(&self, i: u64, j: u64) -> (Color, Vec<SampleLog>) {
+        let mut samples = Vec::new();
+        let (color, _) = self.sample_pixel(i, j, Some(&mut samples));
+        (color, samples)
+    }
+
+    fn sample_pixel(&self, i: u64, j: u64, mut log: Option<&mut Vec<SampleLog>>) -> (Color, AovSample) {
+        random::seed_pixel(self.random_seed, i, j);
+        let mut rng = random::rng();
+
         let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-        for _ in 0..self.samples_per_pixel {
-            let mut rng = rand::thread_rng();
+        let mut aovs = AovAccumulator::new();
+        for sample in 0..self.samples_per_pixel {
             let random_u: f64 = rng.gen();
             let random_v: f64 = rng.gen();
 
             let (u, v) = self.image_size.transform(
                 (i as f64) + random_u, 
                 (j as f64) + random_v);
+            let mut sample_log = log.is_some().then(|| SampleLog::new(u, v));
+            let bounces = sample_log.as_mut().map(|sample| &mut sample.bounces);
+            let (mut color, mut aov) = self.sample(u, v, bounces);
+
+            if !color.is_finite() {
+                self.invalid_samples.fetch_add(1, Ordering::Relaxed);
+                if self.log_invalid_samples {
+                    eprintln!("\nInvalid sample {} of pixel {},{}: {}",
+                        sample, i, self.image_size.height() - 1 - j, color);
+                }
+                let black = Color::new(0.0, 0.0, 0.0);
+                color = black;
+                aov = aov.map_light(|_| black);
+            }
+
+            pixel_color += color;
+            aovs.add(&aov);
+            if let (Some(log), Some(mut sample)) = (log.as_deref_mut(), sample_log) {
+                sample.color = color;
+                log.push(sample);
+            }
+        }
 
-            let r = self.camera.get_ray(u, v);
-            pixel_color += ray_color(&r, &self.world, self.max_depth);
+        (pixel_color / self.samples_per_pixel as f64, aovs.average())
+    }
+
+    /// Color and passes of a camera sample at (u, v).
+    fn sample(&self, u: f64, v: f64, bounces: Option<&mut Vec<Bounce>>) -> (Color, AovSample) {
+        let mut aov = AovSample::background();
+        // Points outside the projection stay black
+        let mut color = match self.camera.get_ray(u, v) {
+            None => Color::new(0.0, 0.0, 0.0),
+            Some(r) => {
+                if let Some((view, max_depth)) = self.debug_view {
+                    debug::shade(view, &r, &self.scene, max_depth)
+                } else if self.spectral {
+                    let wavelengths = Wavelengths::sample(&mut random::rng());
+                    let r = r.with_wavelengths(wavelengths);
+                    let color = self.integrator.radiance(&r, &self.scene, &mut aov, bounces);
+                    aov = aov.map_light(|radiance| wavelengths.to_rgb(radiance));
+                    wavelengths.to_rgb(color)
+                } else {
+                    self.integrator.radiance(&r, &self.scene, &mut aov, bounces)
+                }
+            },
+        };
+
+        if let (Some(max), None) = (self.clamp_indirect, self.debug_view) {
+            aov.indirect = clamp_radiance(aov.indirect, max);
+            color = aov.emission + aov.direct + aov.indirect;
         }
+        (color, aov)
+    }
 
-        (pixel_color / self.samples_per_pixel as f64).sqrt()
+    /// Linear colors of the pixels [x0, x1) x [y0, y1), from the top left
+    /// corner, by Metropolis light transport.
+    fn render_metropolis(&self, metropolis: &Metropolis, [x0, y0, x1, y1]: [u64; 4]) -> Vec<Color> {
+        let height = self.image_size.height();
+        metropolis.render(x1 - x0, y1 - y0, self.samples_per_pixel, self.random_seed, |x, y| {
+            // Pixels are indexed from the bottom row
+            let (i, j) = (x0 as f64 + x, y0 as f64 + y);
+            let (u, v) = self.image_size.transform(i, (height - 1) as f64 - j.floor() + j.fract());
+            let (color, _) = self.sample(u, v, None);
+            if color.is_finite() {
+                color
+            } else {
+                self.invalid_samples.fetch_add(1, Ordering::Relaxed);
+                Color::new(0.0, 0.0, 0.0)
+            }
+        })
     }
 
-    pub fn render_to_image(&self, image_file: &Path) -> Result<(), ImageError> {
-        let buffer = render_buffer(
-            self.image_size, 
-            |i, j| { self.pixel_color(i, j) } );
+    pub fn render_to_image(&self, image_file: &Path) -> Result<(), RenderError> {
+        let size = self.image_size;
+        let [x0, y0, x1, y1] = self.crop.unwrap_or([0, 0, size.width(), size.height()]);
+        self.invalid_samples.store(0, Ordering::Relaxed);
+        let mut cropped = match &self.metropolis {
+            Some(metropolis) if self.debug_view.is_none() => self.render_metropolis(metropolis, [x0, y0, x1, y1])
+                .into_iter()
+                .map(|color| (color, AovSample::background()))
+                .collect(),
+            _ => render_buffer(
+                size,
+                [x0, y0, x1, y1],
+                |i, j| { self.pixel_color(i, j) } ),
+        };
+
+        let count = self.invalid_samples();
+        let total = (x1 - x0) * (y1 - y0) * self.samples_per_pixel;
+        if self.max_invalid_fraction.is_some_and(|max| count as f64 > max * total as f64) {
+            return Err(RenderError::InvalidSamples { count, total });
+        }
+
+        if self.denoise {
+            let (colors, guides): (Vec<Color>, Vec<AovSample>) = cropped.into_iter().unzip();
+            let colors = denoise::denoise(&colors, &guides, x1 - x0, y1 - y0);
+            cropped = colors.into_iter().zip(guides).collect();
+        }
+
+        let (pixels, width, height) = if self.full_frame {
+            // Paste the rendered rows in a black frame
+            let black = (Color::new(0.0, 0.0, 0.0), AovSample::background());
+            let mut pixels = vec![black; (size.width() * size.height()) as usize];
+            let row_length = (x1 - x0) as usize;
+            for (row, y) in cropped.chunks(row_length).zip(y0..y1) {
+                let start = (y * size.width() + x0) as usize;
+                pixels[start..start + row_length].copy_from_slice(row);
+            }
+            (pixels, size.width(), size.height())
+        } else {
+            (cropped, x1 - x0, y1 - y0)
+        };
+        let colors: Vec<Color> = pixels.iter().map(|(color, _)| *color).collect();
+        let aovs: Vec<AovSample> = pixels.iter().map(|(_, aov)| *aov).collect();
+
+        let to_image_error = |e: exr::error::Error| RenderError::Image(ImageError::IoError(io::Error::other(e)));
+        if image_file.extension().is_some_and(|ext| ext == "exr") {
+            // Linear colors, with the passes as extra layers
+            let mut layers = Layers::new(width, height);
+            layers.add_color("beauty", &colors);
+            for &aov in &self.aovs {
+                layers.add_aov(aov, &aovs);
+            }
+            return layers.write(image_file).map_err(to_image_error);
+        }
+
+        for &aov in &self.aovs {
+            let mut layers = Layers::new(width, height);
+            layers.add_aov(aov, &aovs);
+            layers.write(&aov_file(image_file, aov)).map_err(to_image_error)?;
+        }
+
+        let buffer: Vec<u8> = colors.iter()
+            .flat_map(|color| {
+                // Debug views are meant to be seen as they are
+                let color = if self.debug_view.is_some() { *color } else { color.sqrt() };
+                [to_u8(color.x()), to_u8(color.y()), to_u8(color.z())]
+            })
+            .collect();
         
         image::save_buffer(
             image_file, 
             &buffer[..], 
-            self.image_size.width() as u32, 
-            self.image_size.height() as u32, 
-            image::ColorType::Rgb8)
+            width as u32, 
+            height as u32, 
+            image::ColorType::Rgb8)?;
+        Ok(())
     }
 
 }
 
-fn ray_color<H: Hit>(r: &Ray, hittable: &H, depth: u64) -> Color {
-    if depth <= 0 {
-        // If we've exceeded the ray bounce limit, no more light is gathered
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    if let Some(rec) = hittable.hit(r, 0.001, f64::INFINITY) {
-        if let Some((attenuation, scattered)) = rec.material.scatter(r, &rec) {
-            attenuation * ray_color(&scattered, hittable, depth - 1)
-        } else {
-            Color::new(0.0, 0.0, 0.0)
-        }
+/// Color scaled down to keep its largest component within `max`.
+fn clamp_radiance(color: Color, max: f64) -> Color {
+    let largest = color.x().max(color.y()).max(color.z());
+    if largest > max {
+        color * (max / largest)
     } else {
-        let unit_direction = r.direction().normalized();
-        let t = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - t) * Color::new(1.0, 1.0, 1.0) 
-        + t * Color::new(0.5, 0.7, 1.0)
+        color
     }
 }
 
-fn render_buffer<F: Fn(u64, u64) -> Color + Sync + Send>(
+/// File of a pass written next to `image_file`, e.g. `render.depth.exr` for `render.png`.
+fn aov_file(image_file: &Path, aov: Aov) -> PathBuf {
+    let stem = image_file.file_stem().unwrap_or_default().to_string_lossy();
+    image_file.with_file_name(format!("{}.{}.exr", stem, aov.name()))
+}
+
+/// Rows of the pixels [x0, x1) x [y0, y1) of an image, from the top left corner.
+fn render_buffer<T: Send, F: Fn(u64, u64) -> T + Sync + Send>(
     size: Size, 
-    pixel_color: F) -> Vec<u8> {
-    let buffer_size = (size.width() * size.height() * 3) as usize;
-    let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
-    for j in (0..size.height()).rev() {
-        print!("\rScanlines: {:4}", size.height() - j);
+    [x0, y0, x1, y1]: [u64; 4],
+    pixel_color: F) -> Vec<T> {
+    let buffer_size = ((x1 - x0) * (y1 - y0)) as usize;
+    let mut buffer: Vec<T> = Vec::with_capacity(buffer_size);
+    for y in y0..y1 {
+        print!("\rScanlines: {:4}", y - y0 + 1);
         stdout().flush().unwrap();
 
-        let scanline: Vec<Color> = (0..size.width())
+        // Pixels are indexed from the bottom row
+        let j = size.height() - 1 - y;
+        let scanline: Vec<T> = (x0..x1)
             .into_par_iter()
             .map(|i| { pixel_color(i,j) })
             .collect();
 
-        for color in scanline {
-            buffer.push(to_u8(color.x()));
-            buffer.push(to_u8(color.y()));
-            buffer.push(to_u8(color.z()));
-        }
+        buffer.extend(scanline);
     }
     println!("\nDone.");
     buffer
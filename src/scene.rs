@@ -1,19 +1,97 @@
+use std::sync::Arc;
+
 use rand::{Rng, SeedableRng};
 
-use crate::sphere::Sphere;
-use crate::vec::{Color, Point3};
-use crate::material::{Lambertian, Metal, Dielectric};
-use crate::hit::World;
+use crate::aabb::Aabb;
+
+use crate::bvh::Bvh;
+use crate::cone::Cone;
+use crate::cuboid::Cuboid;
+use crate::cylinder::Cylinder;
+use crate::disk::Disk;
+use crate::plane::Plane;
+use crate::quad::Quad;
+use crate::ray::Ray;
+use crate::sphere::{MovingSphere, Sphere};
+use crate::torus::Torus;
+use crate::vec::{Color, Point3, Vec3};
+use crate::material::{Lambertian, Metal, Dielectric, DiffuseLight, MaterialLibrary, Scatter};
+use crate::hit::{Hit, World};
+use crate::instance::Instance;
+use crate::light::AreaLight;
+use crate::medium::{ConstantMedium, PhaseFunction};
+use crate::spectrum::Dispersion;
+use crate::transform::Transform;
+use crate::volume::{GridMedium, GridResolution, VoxelGrid};
+
+/// Light coming from rays that escape the scene.
+#[derive(Clone, Copy)]
+pub enum Background {
+    /// Vertical gradient from white to light blue
+    Sky,
+    Uniform(Color),
+}
 
+impl Background {
+    pub fn color(&self, r: &Ray) -> Color {
+        match self {
+            Background::Sky => {
+                let unit_direction = r.direction().normalized();
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - t) * Color::new(1.0, 1.0, 1.0) 
+                + t * Color::new(0.5, 0.7, 1.0)
+            },
+            Background::Uniform(color) => *color,
+        }
+    }
+}
 
-pub fn random_scene(seed: u64) -> World {
+pub struct Scene {
+    pub world: Bvh,
+    pub background: Background,
+    // Named materials, giving material IDs
+    pub materials: MaterialLibrary,
+    // Emitting objects that integrators can sample
+    pub lights: Vec<AreaLight>,
+    // Suggested viewpoint
+    pub look_from: Point3,
+    pub look_at: Point3,
+    pub vertical_field_of_view: f64,
+    // Suggested shutter interval
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl Scene {
+    /// Scene lit by the sky, seen from the viewpoint of Ray Tracing in One Weekend.
+    pub fn outdoor(world: World) -> Scene {
+        Scene {
+            world: Bvh::new(world),
+            background: Background::Sky,
+            materials: MaterialLibrary::new(),
+            lights: Vec::new(),
+            look_from: Point3::new(13.0, 4.0, -2.0),
+            look_at: Point3::new(0.0, 0.0, 0.0),
+            vertical_field_of_view: 20.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+}
+
+pub fn random_scene(seed: u64) -> Scene {
     let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
     let mut world = World::new();
 
-    let ground_mat = Box::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+    let mut materials = MaterialLibrary::new();
+    materials.insert("ground", Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    materials.insert("glass", Dielectric::new(1.5));
+    materials.insert("green", Lambertian::new(Color::new(0.1, 0.5, 0.1)));
+    materials.insert("red metal", Metal::new(Color::new(0.7, 0.1, 0.1), 0.0));
+
+    let ground = Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), materials["ground"].clone());
 
-    world.push(Box::new(ground_sphere));
+    world.push(Box::new(ground));
 
     for a in -15..=15 {
         for b in -15..=15 {
@@ -25,7 +103,7 @@ pub fn random_scene(seed: u64) -> World {
             if choose_mat < 0.8 {
                 // Diffuse
                 let albedo = Color::random(&mut rng, 0.0..1.0) * Color::random(&mut rng, 0.0..1.0);
-                let sphere_mat = Box::new(Lambertian::new(albedo));
+                let sphere_mat = Arc::new(Lambertian::new(albedo));
                 let sphere = Sphere::new(center, 0.2, sphere_mat);
 
                 world.push(Box::new(sphere));
@@ -33,23 +111,52 @@ pub fn random_scene(seed: u64) -> World {
                 // Metal
                 let albedo = Color::random(&mut rng, 0.4..1.0);
                 let fuzz = rng.gen_range(0.0..0.5);
-                let sphere_mat = Box::new(Metal::new(albedo, fuzz));
+                let sphere_mat = Arc::new(Metal::new(albedo, fuzz));
                 let sphere = Sphere::new(center, 0.2, sphere_mat);
 
                 world.push(Box::new(sphere));
             } else {
                 // Glass
-                let sphere_mat = Box::new(Dielectric::new(1.5));
-                let sphere = Sphere::new(center, 0.2, sphere_mat);
+                let sphere = Sphere::new(center, 0.2, materials["glass"].clone());
 
                 world.push(Box::new(sphere));
             }
         }
     }
 
-    let mat1 = Box::new(Dielectric::new(1.5));
-    let mat2 = Box::new(Lambertian::new(Color::new(0.1, 0.5, 0.1)));
-    let mat3 = Box::new(Metal::new(Color::new(0.7, 0.1, 0.1), 0.0));
+    let sphere1 = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, materials["glass"].clone());
+    let sphere2 = Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, materials["green"].clone());
+    let sphere3 = Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, materials["red metal"].clone());
+
+    world.push(Box::new(sphere1));
+    world.push(Box::new(sphere2));
+    world.push(Box::new(sphere3));
+
+    Scene { materials, ..Scene::outdoor(world) }
+}
+
+/// Dispersive glass spheres of increasing strength, best viewed with `--spectral`.
+pub fn dispersion_scene(seed: u64) -> Scene {
+    let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+    let mut world = World::new();
+
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground = Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground_mat);
+
+    world.push(Box::new(ground));
+
+    // Small spheres with a strong Cauchy dispersion
+    let small_mat: Arc<dyn Scatter> = Arc::new(Dielectric::dispersive(Dispersion::Cauchy { a: 1.5, b: 0.02 }));
+    for a in -6..=6 {
+        let center = Point3::new(a as f64, 0.2, 2.0 + rng.gen_range(0.0..0.5));
+        let sphere = Sphere::new(center, 0.2, small_mat.clone());
+
+        world.push(Box::new(sphere));
+    }
+
+    let mat1 = Arc::new(Dielectric::dispersive(Dispersion::BK7));
+    let mat2 = Arc::new(Dielectric::dispersive(Dispersion::DIAMOND));
+    let mat3 = Arc::new(Dielectric::dispersive(Dispersion::SF11));
 
     let sphere1 = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, mat1);
     let sphere2 = Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, mat2);
@@ -59,5 +166,283 @@ pub fn random_scene(seed: u64) -> World {
     world.push(Box::new(sphere2));
     world.push(Box::new(sphere3));
 
-    world
+    Scene::outdoor(world)
+}
+
+/// Participating media bounded by spheres.
+pub fn volumes_scene() -> Scene {
+    let mut world = World::new();
+
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground = Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground_mat);
+
+    world.push(Box::new(ground));
+
+    // The material of the boundaries is never used for shading
+    let boundary_mat: Arc<dyn Scatter> = Arc::new(Dielectric::new(1.0));
+
+    // Dark isotropic smoke
+    let boundary1 = Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, boundary_mat.clone());
+    let smoke = ConstantMedium::new(
+        Box::new(boundary1), 2.0, Color::new(0.2, 0.2, 0.2), PhaseFunction::Isotropic);
+
+    // Bright forward scattering cloud
+    let boundary2 = Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, boundary_mat.clone());
+    let cloud = ConstantMedium::new(
+        Box::new(boundary2), 4.0, Color::new(0.95, 0.95, 0.95), PhaseFunction::HenyeyGreenstein(0.8));
+
+    // Glass sphere filled with a dense medium, for a subsurface look
+    let glass = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, Arc::new(Dielectric::new(1.5)));
+    let boundary3 = Sphere::new(Point3::new(0.0, 1.0, 0.0), 0.999, boundary_mat);
+    let jade = ConstantMedium::new(
+        Box::new(boundary3), 10.0, Color::new(0.4, 0.9, 0.5), PhaseFunction::Isotropic);
+
+    world.push(Box::new(smoke));
+    world.push(Box::new(cloud));
+    world.push(Box::new(glass));
+    world.push(Box::new(jade));
+
+    Scene::outdoor(world)
+}
+
+/// Procedural cloud made of overlapping Gaussian puffs.
+pub fn procedural_cloud(seed: u64) -> VoxelGrid {
+    let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+    let puffs: Vec<(Point3, f64)> = (0..16)
+        .map(|_| (
+            Point3::new(rng.gen_range(0.3..0.7), rng.gen_range(0.25..0.5), rng.gen_range(0.3..0.7)),
+            rng.gen_range(0.08..0.2)))
+        .collect();
+
+    VoxelGrid::from_fn(GridResolution::new(64, 64, 64), |p| {
+        puffs.iter()
+            .map(|&(center, radius)| (-(p - center).length().powi(2) / radius.powi(2)).exp())
+            .sum::<f64>()
+            .min(1.0)
+    })
+}
+
+/// Heterogeneous medium from a voxel grid, floating above the ground.
+pub fn cloud_scene(grid: VoxelGrid, bounds: Aabb) -> Scene {
+    let mut world = World::new();
+
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground = Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground_mat);
+
+    world.push(Box::new(ground));
+
+    let cloud = GridMedium::new(
+        grid, bounds, 10.0, Color::new(0.9, 0.9, 0.9), PhaseFunction::HenyeyGreenstein(0.5));
+
+    world.push(Box::new(cloud));
+
+    Scene::outdoor(world)
+}
+
+/// Thousands of pebbles, each a transformed instance of one of three shared spheres.
+pub fn instances_scene(seed: u64) -> Scene {
+    let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+    let mut world = World::new();
+
+    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground = Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), ground_mat);
+
+    world.push(Box::new(ground));
+
+    let origin = Point3::new(0.0, 0.0, 0.0);
+    let pebbles: [Arc<dyn Hit>; 3] = [
+        Arc::new(Sphere::new(origin, 1.0, Arc::new(Lambertian::new(Color::new(0.6, 0.4, 0.3))))),
+        Arc::new(Sphere::new(origin, 1.0, Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.3)))),
+        Arc::new(Sphere::new(origin, 1.0, Arc::new(Dielectric::new(1.5)))),
+    ];
+
+    // Pebbles on a sunflower spiral
+    let count = 2000;
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0_f64.sqrt());
+    for i in 0..count {
+        let radius = 8.0 * ((i as f64 + 0.5) / count as f64).sqrt();
+        let angle = (i as f64) * golden_angle;
+        let size = rng.gen_range(0.06..0.12);
+
+        let transform = Transform::translate(Vec3::new(radius * angle.cos(), size * 0.5, radius * angle.sin()))
+            * Transform::rotate_y(rng.gen_range(0.0..360.0))
+            * Transform::scale(Vec3::new(size * 1.6, size * 0.5, size));
+
+        let pebble = &pebbles[rng.gen_range(0..pebbles.len())];
+        world.push(Box::new(Instance::new(pebble.clone(), transform)));
+    }
+
+    // A large tilted glass disc in the middle
+    let lens = Transform::translate(Vec3::new(0.0, 1.0, 0.0))
+        * Transform::rotate(Vec3::new(1.0, 0.0, 1.0), 60.0)
+        * Transform::scale(Vec3::new(1.0, 0.25, 1.0));
+    world.push(Box::new(Instance::new(pebbles[2].clone(), lens)));
+
+    Scene::outdoor(world)
+}
+
+/// Cornell box with two rotated boxes, as in Ray Tracing: The Next Week.
+pub fn cornell_box() -> Scene {
+    let mut world = World::new();
+
+    let mut materials = MaterialLibrary::new();
+    materials.insert("red", Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    materials.insert("white", Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    materials.insert("green", Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    materials.insert("light", DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+
+    world.push(Box::new(Quad::yz_rect(0.0, 555.0, 0.0, 555.0, 555.0, materials["green"].clone())));
+    world.push(Box::new(Quad::yz_rect(0.0, 555.0, 0.0, 555.0, 0.0, materials["red"].clone())));
+    let light = Quad::xz_rect(213.0, 343.0, 227.0, 332.0, 554.0, materials["light"].clone());
+    world.push(Box::new(light.clone()));
+    let lights = vec![AreaLight::new(light, world.len())];
+    world.push(Box::new(Quad::xz_rect(0.0, 555.0, 0.0, 555.0, 0.0, materials["white"].clone())));
+    world.push(Box::new(Quad::xz_rect(0.0, 555.0, 0.0, 555.0, 555.0, materials["white"].clone())));
+    world.push(Box::new(Quad::xy_rect(0.0, 555.0, 0.0, 555.0, 555.0, materials["white"].clone())));
+
+    let tall_box = Arc::new(Cuboid::new(
+        Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), materials["white"].clone()));
+    let tall_transform = Transform::translate(Vec3::new(265.0, 0.0, 295.0)) * Transform::rotate_y(15.0);
+    world.push(Box::new(Instance::new(tall_box, tall_transform)));
+
+    let short_box = Arc::new(Cuboid::new(
+        Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 165.0, 165.0), materials["white"].clone()));
+    let short_transform = Transform::translate(Vec3::new(130.0, 0.0, 65.0)) * Transform::rotate_y(-18.0);
+    world.push(Box::new(Instance::new(short_box, short_transform)));
+
+    Scene {
+        world: Bvh::new(world),
+        background: Background::Uniform(Color::new(0.0, 0.0, 0.0)),
+        materials,
+        lights,
+        look_from: Point3::new(278.0, 278.0, -800.0),
+        look_at: Point3::new(278.0, 278.0, 0.0),
+        vertical_field_of_view: 40.0,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+    }
+}
+
+/// Cylinders, cones, disks, annuli and tori.
+pub fn quadrics_scene() -> Scene {
+    let mut world = World::new();
+
+    let mut materials = MaterialLibrary::new();
+    materials.insert("ground", Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    materials.insert("blue", Lambertian::new(Color::new(0.1, 0.2, 0.5)));
+    materials.insert("orange", Lambertian::new(Color::new(0.8, 0.4, 0.1)));
+    materials.insert("gold", Metal::new(Color::new(0.8, 0.6, 0.2), 0.1));
+    materials.insert("steel", Metal::new(Color::new(0.7, 0.7, 0.7), 0.3));
+    materials.insert("glass", Dielectric::new(1.5));
+
+    world.push(Box::new(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), materials["ground"].clone())));
+
+    // Capped and open cylinders
+    world.push(Box::new(Cylinder::new(Point3::new(-4.5, 0.0, 0.0), 0.6, 1.5, true, materials["blue"].clone())));
+    world.push(Box::new(Cylinder::new(Point3::new(-3.0, 0.0, 1.0), 0.5, 1.0, false, materials["steel"].clone())));
+
+    // Cones
+    world.push(Box::new(Cone::new(Point3::new(-1.5, 0.0, 0.0), 0.6, 1.8, true, materials["orange"].clone())));
+    let cone = Arc::new(Cone::new(Point3::new(0.0, 0.0, 0.0), 0.5, 1.2, true, materials["glass"].clone()));
+    let lying = Transform::translate(Vec3::new(-0.2, 0.5, 1.6)) * Transform::rotate(Vec3::new(0.0, 0.0, 1.0), 90.0);
+    world.push(Box::new(Instance::new(cone, lying)));
+
+    // Disk and annulus
+    world.push(Box::new(Disk::new(Point3::new(1.2, 1.0, -0.5), Vec3::new(0.3, 0.2, 1.0), 0.8, materials["gold"].clone())));
+    world.push(Box::new(Disk::annulus(Point3::new(1.5, 0.01, 1.5), Vec3::new(0.0, 1.0, 0.0), 0.4, 0.7, materials["blue"].clone())));
+
+    // Tori, lying down and standing up
+    world.push(Box::new(Torus::new(Point3::new(3.5, 0.25, 1.0), 0.7, 0.25, materials["orange"].clone())));
+    let torus = Arc::new(Torus::new(Point3::new(0.0, 0.0, 0.0), 0.7, 0.2, materials["gold"].clone()));
+    let standing = Transform::translate(Vec3::new(4.0, 0.9, -0.8)) * Transform::rotate(Vec3::new(1.0, 0.0, 0.0), 80.0);
+    world.push(Box::new(Instance::new(torus, standing)));
+
+    Scene {
+        look_from: Point3::new(0.0, 3.0, 12.0),
+        look_at: Point3::new(0.0, 0.7, 0.0),
+        vertical_field_of_view: 30.0,
+        materials,
+        ..Scene::outdoor(world)
+    }
+}
+
+/// Diffuse spheres bouncing up during the shutter interval [0, 1],
+/// plus a box sliding along the ground.
+pub fn motion_blur_scene(seed: u64) -> Scene {
+    let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+    let mut world = World::new();
+
+    let mut materials = MaterialLibrary::new();
+    materials.insert("ground", Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    materials.insert("glass", Dielectric::new(1.5));
+    materials.insert("red metal", Metal::new(Color::new(0.7, 0.1, 0.1), 0.0));
+    materials.insert("blue", Lambertian::new(Color::new(0.1, 0.2, 0.5)));
+
+    world.push(Box::new(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), materials["ground"].clone())));
+
+    for a in -8..=8 {
+        for b in -8..=8 {
+            let center = Point3::new((a as f64) + rng.gen_range(0.0..0.9),
+                                     0.2,
+                                     (b as f64) + rng.gen_range(0.0..0.9));
+            let albedo = Color::random(&mut rng, 0.0..1.0) * Color::random(&mut rng, 0.0..1.0);
+            let center1 = center + Vec3::new(0.0, rng.gen_range(0.0..0.5), 0.0);
+            let sphere = MovingSphere::new(center, center1, 0.0, 1.0, 0.2, Arc::new(Lambertian::new(albedo)));
+
+            world.push(Box::new(sphere));
+        }
+    }
+
+    world.push(Box::new(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, materials["glass"].clone())));
+    world.push(Box::new(Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, materials["red metal"].clone())));
+
+    // A box sliding and turning a little while the shutter is open
+    let cube = Arc::new(Cuboid::new(Point3::new(-0.7, 0.0, -0.7), Point3::new(0.7, 1.4, 0.7), materials["blue"].clone()));
+    let start = Transform::translate(Vec3::new(4.0, 0.0, 0.8));
+    let end = Transform::translate(Vec3::new(4.0, 0.0, -0.8)) * Transform::rotate_y(15.0);
+    world.push(Box::new(Instance::moving(cube, start, end, 0.0, 1.0)));
+
+    Scene {
+        materials,
+        shutter_open: 0.0,
+        shutter_close: 1.0,
+        ..Scene::outdoor(world)
+    }
+}
+
+/// Glass spheres on the ground under two small lights, focusing them into caustics.
+pub fn caustics_scene() -> Scene {
+    let mut world = World::new();
+
+    let mut materials = MaterialLibrary::new();
+    materials.insert("ground", Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    materials.insert("glass", Dielectric::new(1.5));
+    materials.insert("mirror", Metal::new(Color::new(0.9, 0.9, 0.9), 0.0));
+    materials.insert("light", DiffuseLight::new(Color::new(60.0, 60.0, 60.0)));
+
+    world.push(Box::new(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), materials["ground"].clone())));
+    world.push(Box::new(Quad::xy_rect(-10.0, 10.0, 0.0, 10.0, 6.0, materials["ground"].clone())));
+
+    world.push(Box::new(Sphere::new(Point3::new(-1.5, 1.0, 0.0), 1.0, materials["glass"].clone())));
+    world.push(Box::new(Sphere::new(Point3::new(1.2, 0.6, -0.5), 0.6, materials["glass"].clone())));
+    world.push(Box::new(Sphere::new(Point3::new(1.5, 0.8, 2.0), 0.8, materials["mirror"].clone())));
+
+    let mut lights = Vec::new();
+    for x in [-1.8, 1.0] {
+        let light = Quad::xz_rect(x - 0.25, x + 0.25, -0.25, 0.25, 5.0, materials["light"].clone());
+        world.push(Box::new(light.clone()));
+        lights.push(AreaLight::new(light, world.len()));
+    }
+
+    Scene {
+        world: Bvh::new(world),
+        background: Background::Uniform(Color::new(0.0, 0.0, 0.0)),
+        materials,
+        lights,
+        look_from: Point3::new(0.0, 5.0, -10.0),
+        look_at: Point3::new(0.0, 0.5, 0.5),
+        vertical_field_of_view: 35.0,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+    }
 }
@@ -36,7 +36,106 @@ impl FromStr for Size {
 
             Ok(Size { width, height })
         } else {
-            Err(format!("Expected format: <WIDTH>x<HEIGHT> (e.g. 800x600)."))
+            Err("Expected format: <WIDTH>x<HEIGHT> (e.g. 800x600).".to_string())
         }
     }
-}
\ No newline at end of file
+}
+
+/// Pixel coordinates, from the top left corner of the image.
+#[derive(Clone, Copy)]
+pub struct Pixel {
+    x: u64,
+    y: u64,
+}
+
+impl Pixel {
+    /// Normalized coordinates of the pixel center, as given by `Size::transform`,
+    /// `None` if the pixel lies outside the image.
+    pub fn uv(&self, size: Size) -> Option<(f64, f64)> {
+        if self.x < size.width && self.y < size.height {
+            Some(size.transform(self.x as f64 + 0.5, (size.height - 1 - self.y) as f64 + 0.5))
+        } else {
+            None
+        }
+    }
+
+    /// Pixel indices (i, j) counted from the bottom left corner, as used by
+    /// `Render::pixel_color`, `None` if the pixel lies outside the image.
+    pub fn indices(&self, size: Size) -> Option<(u64, u64)> {
+        if self.x < size.width && self.y < size.height {
+            Some((self.x, size.height - 1 - self.y))
+        } else {
+            None
+        }
+    }
+
+    pub fn x(&self) -> u64 { self.x }
+    pub fn y(&self) -> u64 { self.y }
+}
+
+impl Display for Pixel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{},{}", self.x, self.y))
+    }
+}
+
+impl FromStr for Pixel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        if let [Ok(x), Ok(y)] = s.split(",")
+            .map(|a| a.parse::<u64>())
+            .collect::<Vec<_>>()[..] {
+
+            Ok(Pixel { x, y })
+        } else {
+            Err("Expected format: <X>,<Y> (e.g. 400,300).".to_string())
+        }
+    }
+}
+
+/// Rectangle of an image from (x0, y0) to (x1, y1), measured from the top left
+/// corner. Given in pixels, or in normalized coordinates in [0, 1] if any of the
+/// values has a decimal point.
+#[derive(Clone, Copy)]
+pub struct Crop {
+    bounds: [f64; 4],
+    normalized: bool,
+}
+
+impl Crop {
+    /// Pixel range [x0, x1) x [y0, y1) covered in an image of the given size,
+    /// `None` if it is empty or exceeds the image.
+    pub fn pixels(&self, size: Size) -> Option<[u64; 4]> {
+        let [x0, y0, x1, y1] = if self.normalized {
+            let (w, h) = (size.width as f64, size.height as f64);
+            let [x0, y0, x1, y1] = self.bounds;
+            [(x0 * w).floor(), (y0 * h).floor(), (x1 * w).ceil(), (y1 * h).ceil()]
+        } else {
+            self.bounds
+        };
+
+        if x0 < 0.0 || y0 < 0.0 || x1 > size.width as f64 || y1 > size.height as f64 || x0 >= x1 || y0 >= y1 {
+            None
+        } else {
+            Some([x0 as u64, y0 as u64, x1 as u64, y1 as u64])
+        }
+    }
+}
+
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let normalized = s.contains('.');
+        let values = s.split(",")
+            .map(|a| if normalized { a.parse::<f64>().ok() } else { a.parse::<u64>().ok().map(|x| x as f64) })
+            .collect::<Vec<_>>();
+
+        if let [Some(x0), Some(y0), Some(x1), Some(y1)] = values[..] {
+            Ok(Crop { bounds: [x0, y0, x1, y1], normalized })
+        } else {
+            Err("Expected format: <X0>,<Y0>,<X1>,<Y1> in pixels or normalized (e.g. 100,50,300,200 or 0.25,0.5,0.75,1.0).".to_string())
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::OnceLock;
+
+use rand::Rng;
+
+use super::ray::Ray;
+use super::vec::Color;
+
+// Visible range covered by the RGB to spectrum upsampling tables (in nm)
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 720.0;
+
+// Wavelength of the sodium D line, used for the nominal index of refraction
+pub const D_LINE: f64 = 587.6;
+
+// Each ray carries one wavelength per color channel
+const COUNT: usize = 3;
+
+/// Wavelengths carried by a ray in spectral mode, sampled with
+/// hero wavelength sampling: the hero wavelength is uniformly distributed
+/// and the secondary ones are equally spaced across the visible range.
+#[derive(Clone, Copy)]
+pub struct Wavelengths {
+    lambda: [f64; COUNT],
+    secondary_terminated: bool,
+}
+
+impl Wavelengths {
+    pub fn sample<R: Rng>(rng: &mut R) -> Wavelengths {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = rng.gen_range(0.0..range);
+
+        let mut lambda = [0.0; COUNT];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            *l = LAMBDA_MIN + (hero + (i as f64) * range / (COUNT as f64)) % range;
+        }
+
+        Wavelengths { lambda, secondary_terminated: false }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub fn is_secondary_terminated(&self) -> bool {
+        self.secondary_terminated
+    }
+
+    /// Drops the secondary wavelengths, e.g. after a dispersive event
+    /// where only the hero wavelength can follow the refracted direction.
+    pub fn terminate_secondary(self) -> Wavelengths {
+        Wavelengths { secondary_terminated: true, ..self }
+    }
+
+    /// Samples the spectrum upsampled from an RGB color at each wavelength.
+    pub fn sample_rgb(&self, rgb: Color) -> Color {
+        let mut values = Color::new(0.0, 0.0, 0.0);
+        for (i, &l) in self.lambda.iter().enumerate() {
+            if i == 0 || !self.secondary_terminated {
+                values[i] = rgb_to_spectrum(rgb, l);
+            }
+        }
+        values
+    }
+
+    /// Converts the radiance carried at each wavelength to linear sRGB,
+    /// white balanced so that a constant spectrum maps to a neutral gray.
+    pub fn to_rgb(self, radiance: Color) -> Color {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut xyz = Color::new(0.0, 0.0, 0.0);
+        for (i, &l) in self.lambda.iter().enumerate() {
+            xyz += (radiance[i] * range / (COUNT as f64)) * cie_xyz(l);
+        }
+
+        xyz_to_rgb(xyz) / white_point()
+    }
+}
+
+/// Converts an RGB reflectance into the values carried along `ray`,
+/// taking into account secondary wavelengths terminated by `scattered`.
+pub fn reflectance(ray: &Ray, scattered: &Ray, rgb: Color) -> Color {
+    match (ray.wavelengths(), scattered.wavelengths()) {
+        (Some(incoming), Some(outgoing)) => {
+            let values = outgoing.sample_rgb(rgb);
+            if outgoing.is_secondary_terminated() && !incoming.is_secondary_terminated() {
+                // The hero wavelength now carries the whole estimate
+                (COUNT as f64) * values
+            } else {
+                values
+            }
+        },
+        _ => rgb,
+    }
+}
+
+/// Converts an RGB radiance into the values carried along `ray`.
+pub fn radiance(ray: &Ray, rgb: Color) -> Color {
+    values(ray.wavelengths(), rgb)
+}
+
+/// Converts an RGB color into the values carried at the given wavelengths, if any.
+pub fn values(wavelengths: Option<Wavelengths>, rgb: Color) -> Color {
+    match wavelengths {
+        Some(wavelengths) => wavelengths.sample_rgb(rgb),
+        None => rgb,
+    }
+}
+
+/// Weight of a path joining two subpaths carrying the given wavelengths:
+/// when both of them dropped their secondary wavelengths, each one already
+/// weighs the hero wavelength for all of them.
+pub fn join(a: Option<Wavelengths>, b: Option<Wavelengths>) -> f64 {
+    match (a, b) {
+        (Some(a), Some(b)) if a.is_secondary_terminated() && b.is_secondary_terminated() => 1.0 / (COUNT as f64),
+        _ => 1.0,
+    }
+}
+
+/// Wavelength dependence of the index of refraction of a dielectric.
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    /// Cauchy's equation: n = a + b / λ² (λ in μm)
+    Cauchy { a: f64, b: f64 },
+    /// Sellmeier equation: n² = 1 + Σ bᵢ λ² / (λ² - cᵢ) (λ in μm)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Borosilicate crown glass
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+
+    /// Dense flint glass, used for prisms
+    pub const SF11: Dispersion = Dispersion::Sellmeier {
+        b: [1.73759695, 0.313747346, 1.89878101],
+        c: [0.013188707, 0.0623068142, 155.23629],
+    };
+
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [4.3356, 0.3306, 0.0],
+        c: [0.1060 * 0.1060, 0.1750 * 0.1750, 0.0],
+    };
+
+    pub fn index_of_refraction(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + b.iter()
+                    .zip(c.iter())
+                    .map(|(b, c)| b * l2 / (l2 - c))
+                    .sum::<f64>();
+                n2.sqrt()
+            },
+        }
+    }
+}
+
+// Smits' basis spectra for RGB to spectrum conversion, tabulated over
+// 10 equally sized bins between LAMBDA_MIN and LAMBDA_MAX
+const SPECTRUM_WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SPECTRUM_CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SPECTRUM_MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SPECTRUM_YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SPECTRUM_RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SPECTRUM_GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SPECTRUM_BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+fn basis(table: &[f64; 10], wavelength: f64) -> f64 {
+    // Linear interpolation between bin centers
+    let bin_width = (LAMBDA_MAX - LAMBDA_MIN) / 10.0;
+    let x = ((wavelength - LAMBDA_MIN) / bin_width - 0.5).clamp(0.0, 9.0);
+    let i = (x as usize).min(8);
+    let f = x - i as f64;
+    (1.0 - f) * table[i] + f * table[i + 1]
+}
+
+fn rgb_to_spectrum(rgb: Color, wavelength: f64) -> f64 {
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    let at = |table| basis(table, wavelength);
+
+    if r <= g && r <= b {
+        r * at(&SPECTRUM_WHITE) + if g <= b {
+            (g - r) * at(&SPECTRUM_CYAN) + (b - g) * at(&SPECTRUM_BLUE)
+        } else {
+            (b - r) * at(&SPECTRUM_CYAN) + (g - b) * at(&SPECTRUM_GREEN)
+        }
+    } else if g <= r && g <= b {
+        g * at(&SPECTRUM_WHITE) + if r <= b {
+            (r - g) * at(&SPECTRUM_MAGENTA) + (b - r) * at(&SPECTRUM_BLUE)
+        } else {
+            (b - g) * at(&SPECTRUM_MAGENTA) + (r - b) * at(&SPECTRUM_RED)
+        }
+    } else {
+        b * at(&SPECTRUM_WHITE) + if r <= g {
+            (r - b) * at(&SPECTRUM_YELLOW) + (g - r) * at(&SPECTRUM_GREEN)
+        } else {
+            (g - b) * at(&SPECTRUM_YELLOW) + (r - g) * at(&SPECTRUM_RED)
+        }
+    }
+}
+
+fn piecewise_gaussian(x: f64, mu: f64, sigma_left: f64, sigma_right: f64) -> f64 {
+    let sigma = if x < mu { sigma_left } else { sigma_right };
+    (-0.5 * ((x - mu) / sigma).powi(2)).exp()
+}
+
+/// CIE 1931 color matching functions, using the multi-lobe fit
+/// by Wyman, Sloan and Shirley.
+fn cie_xyz(wavelength: f64) -> Color {
+    let g = |mu, s1, s2| piecewise_gaussian(wavelength, mu, s1, s2);
+    Color::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8))
+}
+
+fn xyz_to_rgb(xyz: Color) -> Color {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    Color::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z)
+}
+
+/// Linear sRGB response to a constant unit spectrum.
+fn white_point() -> Color {
+    static WHITE_POINT: OnceLock<Color> = OnceLock::new();
+    *WHITE_POINT.get_or_init(|| {
+        let steps = 1000;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / (steps as f64);
+        let mut xyz = Color::new(0.0, 0.0, 0.0);
+        for i in 0..steps {
+            xyz += step * cie_xyz(LAMBDA_MIN + (i as f64 + 0.5) * step);
+        }
+        xyz_to_rgb(xyz)
+    })
+}
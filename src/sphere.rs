@@ -1,44 +1,117 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
 use super::hit::{Hit, HitRecord};
 use super::material::Scatter;
 use super::ray::Ray;
-use super::vec::Point3;
+use super::vec::{Point3, Vec3};
 
 pub struct Sphere {
     center: Point3,
     radius: f64,
-    material: Box<dyn Scatter>,
+    material: Arc<dyn Scatter>,
 }
 
 impl Sphere {
-    pub fn new(center: Point3, radius: f64, material: Box<dyn Scatter>) -> Sphere {
+    pub fn new(center: Point3, radius: f64, material: Arc<dyn Scatter>) -> Sphere {
         Sphere { center, radius, material }
     }
+
+    /// Texture coordinates of a point on the unit sphere: u is the angle
+    /// around the y axis from x = -1, v the angle from y = -1, both in [0, 1].
+    fn uv(p: Point3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + std::f64::consts::PI;
+        (phi / (2.0 * std::f64::consts::PI), theta / std::f64::consts::PI)
+    }
 }
 
 impl Hit for Sphere {
     
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = r.origin() - self.center;
-        let a = r.direction().length().powi(2);
-        let half_b = oc.dot(r.direction());
-        let c = oc.length().powi(2) - self.radius.powi(2);
-        let discriminant = half_b.powi(2) - a * c;
-        if discriminant < 0.0 {
-            return None;
-        } 
-        
-        // Find the nearest root that lies in the acceptable range
-        let sqrtd = discriminant.sqrt();
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center, self.radius, self.material.as_ref(), r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::from_points(self.center - extent, self.center + extent))
+    }
+}
+
+/// Sphere moving linearly from `center0` at `time0` to `center1` at `time1`.
+pub struct MovingSphere {
+    center0: Point3,
+    center1: Point3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Arc<dyn Scatter>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point3, 
+        center1: Point3, 
+        time0: f64, 
+        time1: f64, 
+        radius: f64, 
+        material: Arc<dyn Scatter>) -> MovingSphere {
+
+        MovingSphere { center0, center1, time0, time1, radius, material }
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
         }
-        
-        let p = r.at(root);
-        let outward_normal = (p - self.center) / self.radius;
-        Some(HitRecord::new(r, root, outward_normal, self.material.as_ref()))
+        let s = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + s * (self.center1 - self.center0)
     }
 }
+
+impl Hit for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center(r.time()), self.radius, self.material.as_ref(), r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Enclose the whole motion
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::surrounding(
+            Aabb::from_points(self.center0 - extent, self.center0 + extent),
+            Aabb::from_points(self.center1 - extent, self.center1 + extent)))
+    }
+}
+
+fn hit_sphere<'a>(
+    center: Point3, 
+    radius: f64, 
+    material: &'a dyn Scatter, 
+    r: &Ray, 
+    t_min: f64, 
+    t_max: f64) -> Option<HitRecord<'a>> {
+
+    let oc = r.origin() - center;
+    let a = r.direction().length().powi(2);
+    let half_b = oc.dot(r.direction());
+    let c = oc.length().powi(2) - radius.powi(2);
+    let discriminant = half_b.powi(2) - a * c;
+    if discriminant < 0.0 {
+        return None;
+    } 
+    
+    // Find the nearest root that lies in the acceptable range
+    let sqrtd = discriminant.sqrt();
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || t_max < root {
+        root = (-half_b + sqrtd) / a;
+        if root < t_min || t_max < root {
+            return None;
+        }
+    }
+    
+    let p = r.at(root);
+    let outward_normal = (p - center) / radius;
+    let (u, v) = Sphere::uv(outward_normal);
+    Some(HitRecord::new(r, root, outward_normal, u, v, material))
+}
\ No newline at end of file
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::ray::Ray;
+use super::roots;
+use super::vec::{Point3, Vec3};
+
+/// Torus around the y axis through `center`, with the given distance from
+/// the center to the middle of the tube and radius of the tube.
+/// Use an `Instance` for other orientations.
+pub struct Torus {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+    material: Arc<dyn Scatter>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3, 
+        major_radius: f64, 
+        minor_radius: f64, 
+        material: Arc<dyn Scatter>) -> Torus {
+
+        Torus { center, major_radius, minor_radius, material }
+    }
+}
+
+impl Hit for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+
+        // Work with a unit direction, starting close to the torus:
+        // smaller coefficients make the quartic better conditioned
+        let length = r.direction().length();
+        let d = r.direction() / length;
+        let o = r.origin() - self.center;
+        let bounding_radius = big_r + small_r;
+        let start = (-o.dot(d) - bounding_radius).max(0.0);
+        let o = o + start * d;
+
+        // (|p|² + R² - r²)² = 4R²(x² + z²) expanded along p = o + s d
+        let f = o.dot(d);
+        let e = o.dot(o) - big_r * big_r - small_r * small_r;
+        let four_r2 = 4.0 * big_r * big_r;
+        let coefficients = [
+            e * e - four_r2 * (small_r * small_r - o.y() * o.y()),
+            4.0 * f * e + 2.0 * four_r2 * o.y() * d.y(),
+            2.0 * e + 4.0 * f * f + four_r2 * d.y() * d.y(),
+            4.0 * f,
+            1.0,
+        ];
+
+        let t = roots::solve_quartic(coefficients)
+            .into_iter()
+            .map(|s| (start + s) / length)
+            .filter(|&t| t_min <= t && t <= t_max)
+            .min_by(f64::total_cmp)?;
+
+        let p = r.at(t) - self.center;
+        let sum = p.dot(p) - big_r * big_r - small_r * small_r;
+        let outward_normal = Vec3::new(
+            p.x() * sum, 
+            p.y() * (sum + 2.0 * big_r * big_r), 
+            p.z() * sum).normalized();
+
+        // u is the angle around the y axis, v the angle around the tube
+        let pi = std::f64::consts::PI;
+        let u = ((-p.z()).atan2(p.x()) + pi) / (2.0 * pi);
+        let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - big_r;
+        let v = (p.y().atan2(ring) + pi) / (2.0 * pi);
+
+        Some(HitRecord::new(r, t, outward_normal, u, v, self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(
+            self.major_radius + self.minor_radius, 
+            self.minor_radius, 
+            self.major_radius + self.minor_radius);
+        Some(Aabb::from_points(self.center - extent, self.center + extent))
+    }
+}
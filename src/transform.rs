@@ -0,0 +1,253 @@
+use std::ops::Mul;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+
+/// Row-major 4x4 matrix.
+#[derive(Clone, Copy)]
+pub struct Matrix4([[f64; 4]; 4]);
+
+impl Matrix4 {
+    pub fn identity() -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Matrix4(m)
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.0[j][i];
+            }
+        }
+        Matrix4(m)
+    }
+
+    /// Inverse by Gauss-Jordan elimination, `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.0;
+        let mut inv = Matrix4::identity().0;
+
+        for col in 0..4 {
+            // Partial pivoting
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1.0e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+
+        Some(Matrix4(inv))
+    }
+
+    /// Entry-wise linear interpolation.
+    pub fn lerp(&self, other: &Matrix4, s: f64) -> Matrix4 {
+        let mut m = self.0;
+        for (row, other_row) in m.iter_mut().zip(other.0.iter()) {
+            for (x, y) in row.iter_mut().zip(other_row.iter()) {
+                *x += s * (y - *x);
+            }
+        }
+        Matrix4(m)
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Matrix4(m)
+    }
+}
+
+/// Affine transform, storing its inverse alongside.
+#[derive(Clone, Copy)]
+pub struct Transform {
+    m: Matrix4,
+    inv: Matrix4,
+}
+
+impl Transform {
+    pub fn translate(delta: Vec3) -> Transform {
+        let m = Matrix4([
+            [1.0, 0.0, 0.0, delta.x()],
+            [0.0, 1.0, 0.0, delta.y()],
+            [0.0, 0.0, 1.0, delta.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inv = Matrix4([
+            [1.0, 0.0, 0.0, -delta.x()],
+            [0.0, 1.0, 0.0, -delta.y()],
+            [0.0, 0.0, 1.0, -delta.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Transform { m, inv }
+    }
+
+    /// Non-uniform scaling, all factors must be non-zero.
+    pub fn scale(factors: Vec3) -> Transform {
+        let m = Matrix4([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inv = Matrix4([
+            [1.0 / factors.x(), 0.0, 0.0, 0.0],
+            [0.0, 1.0 / factors.y(), 0.0, 0.0],
+            [0.0, 0.0, 1.0 / factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Transform { m, inv }
+    }
+
+    /// Rotation by `degrees` around `axis` (counterclockwise looking down the axis).
+    pub fn rotate(axis: Vec3, degrees: f64) -> Transform {
+        let a = axis.normalized();
+        let theta = std::f64::consts::PI / 180.0 * degrees;
+        let (sin, cos) = theta.sin_cos();
+
+        let mut m = Matrix4::identity().0;
+        for i in 0..3 {
+            for j in 0..3 {
+                let delta = if i == j { 1.0 } else { 0.0 };
+                m[i][j] = a[i] * a[j] * (1.0 - cos) + delta * cos;
+            }
+        }
+        m[0][1] -= a.z() * sin;
+        m[0][2] += a.y() * sin;
+        m[1][0] += a.z() * sin;
+        m[1][2] -= a.x() * sin;
+        m[2][0] -= a.y() * sin;
+        m[2][1] += a.x() * sin;
+
+        // Rotations are orthogonal
+        let m = Matrix4(m);
+        Transform { m, inv: m.transpose() }
+    }
+
+    pub fn rotate_y(degrees: f64) -> Transform {
+        Transform::rotate(Vec3::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform { m: self.inv, inv: self.m }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        let m = &self.m.0;
+        Point3::new(
+            m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+            m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+            m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3])
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m.0;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z())
+    }
+
+    /// Box enclosing the transformed corners of `b`.
+    pub fn aabb(&self, b: &Aabb) -> Aabb {
+        let corners = b.corners().map(|c| self.point(c));
+        corners[1..].iter()
+            .fold(Aabb::from_points(corners[0], corners[0]), |acc, &c| {
+                Aabb::surrounding(acc, Aabb::from_points(c, c))
+            })
+    }
+
+    /// Transforms a surface normal, using the inverse transpose.
+    /// The result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let inv = &self.inv.0;
+        Vec3::new(
+            inv[0][0] * n.x() + inv[1][0] * n.y() + inv[2][0] * n.z(),
+            inv[0][1] * n.x() + inv[1][1] * n.y() + inv[2][1] * n.z(),
+            inv[0][2] * n.x() + inv[1][2] * n.y() + inv[2][2] * n.z())
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    /// Composition: `a * b` applies `b` first, then `a`.
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform { m: self.m * rhs.m, inv: rhs.inv * self.inv }
+    }
+}
+
+/// Transform interpolated between two keyframes over a time interval,
+/// held constant outside of it.
+///
+/// Matrices are interpolated entry by entry, which is exact for translations
+/// and scalings: rotations between the keyframes should be kept small.
+#[derive(Clone, Copy)]
+pub struct AnimatedTransform {
+    start: Transform,
+    end: Transform,
+    time0: f64,
+    time1: f64,
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Transform, end: Transform, time0: f64, time1: f64) -> AnimatedTransform {
+        AnimatedTransform { start, end, time0, time1 }
+    }
+
+    pub fn fixed(transform: Transform) -> AnimatedTransform {
+        AnimatedTransform::new(transform, transform, 0.0, 0.0)
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        if self.time1 <= self.time0 || time <= self.time0 {
+            return self.start;
+        }
+        if time >= self.time1 {
+            return self.end;
+        }
+
+        let s = (time - self.time0) / (self.time1 - self.time0);
+        let m = self.start.m.lerp(&self.end.m, s);
+        match m.inverse() {
+            Some(inv) => Transform { m, inv },
+            None => self.start,
+        }
+    }
+
+    /// Box enclosing `b` over the whole motion: since transformed points
+    /// move linearly, the boxes at both keyframes suffice.
+    pub fn aabb(&self, b: &Aabb) -> Aabb {
+        Aabb::surrounding(self.start.aabb(b), self.end.aabb(b))
+    }
+}
@@ -1,10 +1,13 @@
 use std::{
     fmt::Display,
     ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign, Range},
+    str::FromStr,
 };
 
 use rand::Rng;
 
+use super::random;
+
 #[derive(Clone, Copy)]
 pub struct Vec3([f64; 3]);
 
@@ -67,6 +70,10 @@ impl Vec3 {
         r_out_perp + r_out_parallel
     }
     
+    pub fn is_finite(self) -> bool {
+        self[0].is_finite() && self[1].is_finite() && self[2].is_finite()
+    }
+
     pub fn near_zero(self) -> bool {
         const EPSILON: f64 = 1.0e-8;
         
@@ -84,9 +91,9 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_sphere() -> Vec3 {
-        let mut thread_rng = rand::thread_rng();
+        let mut rng = random::rng();
         loop {
-            let v = Vec3::random(&mut thread_rng, -1.0..1.0);
+            let v = Vec3::random(&mut rng, -1.0..1.0);
             if v.length() < 1.0 {
                 return v;
             }
@@ -104,7 +111,7 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_disk() -> Vec3 {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
     
         loop {
             let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
@@ -197,6 +204,14 @@ impl Div<f64> for Vec3 {
     }
 }
 
+impl Div<Vec3> for Vec3 {
+    type Output = Vec3;
+
+    fn div(self, rhs: Vec3) -> Vec3 {
+        Vec3([self[0] / rhs[0], self[1] / rhs[1], self[2] / rhs[2]])
+    }
+}
+
 impl DivAssign<f64> for Vec3 {
     fn div_assign(&mut self, rhs: f64) {
         *self = Vec3([self[0] / rhs, self[1] / rhs, self[2] / rhs]);
@@ -208,3 +223,22 @@ impl Display for Vec3 {
         write!(f, "({}, {}, {})", self[0], self[1], self[2])
     }
 }
+
+impl FromStr for Vec3 {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        if let [Ok(x), Ok(y), Ok(z)] = s.split(',')
+            .map(|a| a.trim().parse::<f64>())
+            .collect::<Vec<_>>()[..] {
+
+            if [x, y, z].iter().all(|c| c.is_finite()) {
+                Ok(Vec3([x, y, z]))
+            } else {
+                Err("Components must be finite numbers.".to_string())
+            }
+        } else {
+            Err("Expected format: <X>,<Y>,<Z> (e.g. 13,4,-2).".to_string())
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::medium::{self, PhaseFunction, PhaseMaterial};
+use super::random;
+use super::ray::Ray;
+use super::vec::{Color, Point3};
+
+/// Resolution of a voxel grid.
+#[derive(Clone, Copy)]
+pub struct GridResolution([usize; 3]);
+
+impl GridResolution {
+    pub fn new(x: usize, y: usize, z: usize) -> GridResolution {
+        GridResolution([x, y, z])
+    }
+
+    /// Number of voxels, `None` if it does not fit in a `usize`.
+    pub fn voxel_count(&self) -> Option<usize> {
+        self.0.iter().try_fold(1usize, |count, &n| count.checked_mul(n))
+    }
+}
+
+impl Display for GridResolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}x{}", self.0[0], self.0[1], self.0[2])
+    }
+}
+
+impl FromStr for GridResolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        if let [Ok(x), Ok(y), Ok(z)] = s.split('x')
+            .map(|a| a.parse::<usize>())
+            .collect::<Vec<_>>()[..] {
+
+            let resolution = GridResolution([x, y, z]);
+            match resolution.voxel_count() {
+                Some(_) => Ok(resolution),
+                None => Err(format!("A {} grid is too large.", resolution)),
+            }
+        } else {
+            Err("Expected format: <X>x<Y>x<Z> (e.g. 64x64x64).".to_string())
+        }
+    }
+}
+
+/// Scalar values (e.g. densities) sampled on a regular 3D grid.
+pub struct VoxelGrid {
+    resolution: GridResolution,
+    data: Vec<f64>,
+}
+
+impl VoxelGrid {
+    /// Grid sampling `f` at the voxel centers, in normalized coordinates.
+    pub fn from_fn<F: Fn(Point3) -> f64>(resolution: GridResolution, f: F) -> VoxelGrid {
+        let [nx, ny, nz] = resolution.0;
+        let mut data = Vec::with_capacity(resolution.voxel_count().unwrap_or(0));
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    data.push(f(Point3::new(
+                        (x as f64 + 0.5) / nx as f64,
+                        (y as f64 + 0.5) / ny as f64,
+                        (z as f64 + 0.5) / nz as f64)));
+                }
+            }
+        }
+        VoxelGrid { resolution, data }
+    }
+
+    /// Loads a grid in the Mitsuba `.vol` format (32-bit float encoding),
+    /// returning the bounding box stored in its header as well.
+    /// Only the first channel of multi-channel grids is used.
+    pub fn load_vol(path: &Path) -> io::Result<(VoxelGrid, Aabb)> {
+        let bytes = fs::read(path)?;
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        if bytes.len() < 48 || &bytes[0..3] != b"VOL" || bytes[3] != 3 {
+            return Err(invalid("not a version 3 .vol file"));
+        }
+
+        let int_at = |offset: usize| i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let float_at = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as f64;
+
+        if int_at(4) != 1 {
+            return Err(invalid("only 32-bit float encoding is supported"));
+        }
+
+        let dimensions = [int_at(8), int_at(12), int_at(16), int_at(20)];
+        if dimensions.iter().any(|&d| d <= 0) {
+            return Err(invalid("invalid grid dimensions"));
+        }
+        let [nx, ny, nz, channels] = dimensions.map(|d| d as usize);
+
+        let resolution = GridResolution([nx, ny, nz]);
+        let voxel_count = resolution.voxel_count().ok_or_else(|| invalid("grid too large"))?;
+        let size = voxel_count.checked_mul(4 * channels)
+            .and_then(|n| n.checked_add(48))
+            .ok_or_else(|| invalid("grid too large"))?;
+        if bytes.len() < size {
+            return Err(invalid("truncated voxel data"));
+        }
+
+        let bounds = Aabb::new(
+            Point3::new(float_at(24), float_at(28), float_at(32)),
+            Point3::new(float_at(36), float_at(40), float_at(44)));
+
+        let data = (0..voxel_count)
+            .map(|i| float_at(48 + 4 * channels * i))
+            .collect();
+
+        Ok((VoxelGrid { resolution, data }, bounds))
+    }
+
+    /// Loads a headerless grid of 8-bit values, mapped to [0, 1],
+    /// stored with x varying fastest and z slowest.
+    pub fn load_raw(path: &Path, resolution: GridResolution) -> io::Result<VoxelGrid> {
+        let bytes = fs::read(path)?;
+        let voxel_count = resolution.voxel_count().ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("a {} grid is too large", resolution)))?;
+        if bytes.len() != voxel_count {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected {} bytes for a {} grid, found {}",
+                    voxel_count, resolution, bytes.len())));
+        }
+
+        let data = bytes.iter().map(|&b| (b as f64) / 255.0).collect();
+        Ok(VoxelGrid { resolution, data })
+    }
+
+    pub fn max_value(&self) -> f64 {
+        self.data.iter().cloned().fold(0.0, f64::max)
+    }
+
+    fn at(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.resolution.0;
+        self.data[(z * ny + y) * nx + x]
+    }
+
+    /// Trilinearly interpolated value at normalized coordinates in [0, 1]³.
+    pub fn lookup(&self, p: Point3) -> f64 {
+        let mut index = [[0; 2]; 3];
+        let mut weight = [0.0; 3];
+        for a in 0..3 {
+            // Voxel values are located at the cell centers
+            let n = self.resolution.0[a];
+            let x = (p[a] * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            let i = x.floor() as usize;
+            index[a] = [i, (i + 1).min(n - 1)];
+            weight[a] = x - i as f64;
+        }
+
+        let mut value = 0.0;
+        for (dz, wz) in [(0, 1.0 - weight[2]), (1, weight[2])] {
+            for (dy, wy) in [(0, 1.0 - weight[1]), (1, weight[1])] {
+                for (dx, wx) in [(0, 1.0 - weight[0]), (1, weight[0])] {
+                    value += wx * wy * wz * self.at(index[0][dx], index[1][dy], index[2][dz]);
+                }
+            }
+        }
+        value
+    }
+}
+
+/// Heterogeneous medium with densities given by a voxel grid
+/// stretched over a bounding box.
+pub struct GridMedium {
+    grid: VoxelGrid,
+    bounds: Aabb,
+    density_scale: f64,
+    majorant: f64,
+    phase_material: PhaseMaterial,
+}
+
+impl GridMedium {
+    pub fn new(
+        grid: VoxelGrid,
+        bounds: Aabb,
+        density_scale: f64,
+        albedo: Color,
+        phase_function: PhaseFunction) -> GridMedium {
+
+        let majorant = density_scale * grid.max_value();
+        GridMedium {
+            grid,
+            bounds,
+            density_scale,
+            majorant,
+            phase_material: PhaseMaterial::new(albedo, phase_function),
+        }
+    }
+
+    fn density(&self, p: Point3) -> f64 {
+        let local = (p - self.bounds.min()) / (self.bounds.max() - self.bounds.min());
+        self.density_scale * self.grid.lookup(local)
+    }
+}
+
+impl Hit for GridMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+
+        let (t_enter, t_exit) = self.bounds.hit_range(r, t_min, t_max)?;
+
+        // Delta tracking: sample tentative collisions against the majorant
+        // and accept them as real with probability density / majorant
+        let mut rng = random::rng();
+        let ray_length = r.direction().length();
+        let mut t = t_enter;
+        loop {
+            t += medium::free_flight_distance(self.majorant) / ray_length;
+            if t >= t_exit {
+                return None;
+            }
+
+            if rng.gen::<f64>() * self.majorant < self.density(r.at(t)) {
+                return Some(medium::scattering_record(r, t, &self.phase_material));
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if self.majorant <= 0.0 {
+            return 1.0;
+        }
+
+        let Some((t_enter, t_exit)) = self.bounds.hit_range(r, t_min, t_max) else {
+            return 1.0;
+        };
+
+        // Ratio tracking: instead of stopping at the first real collision,
+        // weight the light by the probability of each tentative one being null
+        let ray_length = r.direction().length();
+        let mut transmittance = 1.0;
+        let mut t = t_enter;
+        loop {
+            t += medium::free_flight_distance(self.majorant) / ray_length;
+            if t >= t_exit || transmittance == 0.0 {
+                return transmittance;
+            }
+
+            transmittance *= 1.0 - self.density(r.at(t)) / self.majorant;
+        }
+    }
+}